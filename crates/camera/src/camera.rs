@@ -1,7 +1,11 @@
 use std::ops::DerefMut;
 
 use bevy::prelude::*;
-use common::states::AppState;
+use common::{
+    coordinates::{TILEMAP_OFFSET, WorldCoordinates},
+    resources::MapBounds,
+    states::AppState,
+};
 use leafwing_input_manager::{
     Actionlike,
     plugin::InputManagerPlugin,
@@ -20,7 +24,16 @@ impl Plugin for CameraPlugin {
             .add_systems(OnEnter(AppState::MainGame), setup)
             .add_systems(
                 Update,
-                (zoom, scroll, pan.after(zoom)).run_if(in_state(AppState::MainGame)),
+                (
+                    zoom,
+                    scroll,
+                    pan.after(zoom),
+                    keep_in_bounds
+                        .after(scroll)
+                        .after(pan)
+                        .run_if(resource_exists::<MapBounds>),
+                )
+                    .run_if(in_state(AppState::MainGame)),
             );
     }
 }
@@ -109,3 +122,23 @@ fn pan(
             (axis_pair.normalize() * camera_settings.pan_rate).extend(0.0);
     }
 }
+
+/// Keeps the center of the camera and its layer within the map. The center can go up to the outer
+/// edges of the tiles along the map edge.
+fn keep_in_bounds(
+    bounds: Res<MapBounds>,
+    query: Single<(&mut Transform, &mut CameraLayer), With<Camera2d>>,
+) {
+    let (mut camera_transform, mut layer) = query.into_inner();
+    // tiles are centered on their blocks, so the map reaches half a tile beyond them
+    let min = WorldCoordinates(bounds.min.as_vec3()).translation() + TILEMAP_OFFSET;
+    let max = WorldCoordinates(bounds.max.as_vec3()).translation() - TILEMAP_OFFSET;
+    let translation = camera_transform.translation.truncate().clamp(min, max);
+    if translation != camera_transform.translation.truncate() {
+        camera_transform.translation = translation.extend(camera_transform.translation.z);
+    }
+    let clamped_layer = layer.0.clamp(bounds.min.z, bounds.max.z);
+    if clamped_layer != layer.0 {
+        layer.0 = clamped_layer;
+    }
+}
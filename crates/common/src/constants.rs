@@ -1,3 +1,6 @@
 use bevy::prelude::*;
 
 pub const TILE_SIZE: Vec2 = Vec2::new(32.0, 32.0);
+
+/// The size of a chunk in blocks.
+pub const CHUNK_SIZE: UVec3 = UVec3::new(16, 16, 16);
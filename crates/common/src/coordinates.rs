@@ -0,0 +1,292 @@
+//! Every space the game deals with and how to convert between them:
+//!
+//! - screen: pixels within the window, as reported for the cursor
+//! - translation: the `Transform` of sprites and tilemaps, [`TILE_SIZE`] per block
+//! - [`WorldCoordinates`]: continuous positions in blocks, z is the layer
+//! - [`IWorldCoordinates`]: a single block. Blocks are centered on their coordinates
+//! - [`ChunkCoordinates`] and [`ChunkBlockCoordinates`]: a chunk and a block within it
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::{CHUNK_SIZE, TILE_SIZE},
+    traits::{Adjacency, Connectivity, Neighbors},
+};
+
+/// Sprites are drawn at this z translation, on top of the tilemaps.
+pub const SPRITE_DEPTH: f32 = 0.1;
+
+/// bevy_ecs_tilemap draws a tile from its position to one tile up and right of it. The world map
+/// is moved by this offset, so every tile is centered on its block like everything else.
+pub const TILEMAP_OFFSET: Vec2 = Vec2::new(-TILE_SIZE.x / 2.0, -TILE_SIZE.y / 2.0);
+
+/// A single block, see [`WorldCoordinates::block`] for which one a position lies in.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Reflect, Serialize)]
+pub struct IWorldCoordinates(pub IVec3);
+
+impl IWorldCoordinates {
+    /// Manually overrides the z value of the coordinates.
+    pub fn with_z_offset(mut self, z_offset: i32) -> IWorldCoordinates {
+        self.0.z = z_offset;
+        self
+    }
+
+    /// Returns how the other coordinates touch these. None if they aren't neighbors.
+    pub fn adjacency(&self, other: &IWorldCoordinates) -> Option<Adjacency> {
+        Adjacency::from_offset(other.0 - self.0)
+    }
+
+    /// Returns the position of the center of the block.
+    pub fn center(&self) -> WorldCoordinates {
+        WorldCoordinates(self.0.as_vec3())
+    }
+
+    /// Returns the chunk these coordinates are in.
+    pub fn chunk(&self) -> ChunkCoordinates {
+        ChunkCoordinates(self.0.div_euclid(CHUNK_SIZE.as_ivec3()))
+    }
+
+    /// Returns the coordinates within the chunk.
+    pub fn chunk_block(&self) -> ChunkBlockCoordinates {
+        ChunkBlockCoordinates(self.0.rem_euclid(CHUNK_SIZE.as_ivec3()).as_uvec3())
+    }
+
+    /// Returns the chunk and the coordinates within it, the inverse of [`ChunkCoordinates::block`].
+    pub fn to_chunk_and_block(&self) -> (ChunkCoordinates, ChunkBlockCoordinates) {
+        (self.chunk(), self.chunk_block())
+    }
+
+    /// Returns true if any of the 26 neighbors lies in another chunk.
+    pub fn is_on_chunk_border(&self) -> bool {
+        let local = self.0.rem_euclid(CHUNK_SIZE.as_ivec3());
+        local.cmpeq(IVec3::ZERO).any() || local.cmpeq(CHUNK_SIZE.as_ivec3() - 1).any()
+    }
+
+    /// Returns the chunks other than its own that connected neighbors lie in, without duplicates.
+    pub fn neighboring_chunks(&self, connectivity: Connectivity) -> Vec<ChunkCoordinates> {
+        let own = self.chunk();
+        let mut chunks: Vec<ChunkCoordinates> = vec![];
+        for (neighbor, _) in self.neighbors(connectivity) {
+            let chunk = neighbor.chunk();
+            if chunk != own && !chunks.contains(&chunk) {
+                chunks.push(chunk);
+            }
+        }
+        chunks
+    }
+}
+
+impl Neighbors<IWorldCoordinates> for IWorldCoordinates {
+    fn same_layer_neighbors(&self) -> Vec<(IWorldCoordinates, u32)> {
+        self.0
+            .same_layer_neighbors()
+            .iter()
+            .map(|(vec, cost)| (IWorldCoordinates(*vec), *cost))
+            .collect()
+    }
+
+    fn all_neighbors(&self) -> Vec<(IWorldCoordinates, u32)> {
+        self.0
+            .all_neighbors()
+            .iter()
+            .map(|(vec, cost)| (IWorldCoordinates(*vec), *cost))
+            .collect()
+    }
+}
+
+/// Coordinates of a chunk within the world
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Reflect)]
+pub struct ChunkCoordinates(pub IVec3);
+
+impl ChunkCoordinates {
+    /// Returns the block with the lowest coordinates in the chunk.
+    pub fn origin(&self) -> IWorldCoordinates {
+        IWorldCoordinates(self.0 * CHUNK_SIZE.as_ivec3())
+    }
+
+    /// Returns the world coordinates of a block within the chunk.
+    pub fn block(&self, block_coordinates: impl Into<ChunkBlockCoordinates>) -> IWorldCoordinates {
+        IWorldCoordinates(self.origin().0 + block_coordinates.into().0.as_ivec3())
+    }
+}
+
+impl Neighbors<ChunkCoordinates> for ChunkCoordinates {
+    fn same_layer_neighbors(&self) -> Vec<(ChunkCoordinates, u32)> {
+        self.0
+            .same_layer_neighbors()
+            .iter()
+            .map(|(vec, cost)| (ChunkCoordinates(*vec), *cost))
+            .collect()
+    }
+
+    fn all_neighbors(&self) -> Vec<(ChunkCoordinates, u32)> {
+        self.0
+            .all_neighbors()
+            .iter()
+            .map(|(vec, cost)| (ChunkCoordinates(*vec), *cost))
+            .collect()
+    }
+}
+
+/// Coordinates of a block within a chunk
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChunkBlockCoordinates(pub UVec3);
+
+impl From<(u32, u32, u32)> for ChunkBlockCoordinates {
+    fn from(value: (u32, u32, u32)) -> Self {
+        ChunkBlockCoordinates(UVec3::new(value.0, value.1, value.2))
+    }
+}
+
+/// Continuous world coordinates, measured in blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Reflect, Component, Deserialize, Serialize)]
+pub struct WorldCoordinates(pub Vec3);
+
+impl WorldCoordinates {
+    /// Returns the coordinates shown at a translation, on the layer `z`.
+    pub fn from_translation(translation: Vec2, z: f32) -> Self {
+        WorldCoordinates((translation / TILE_SIZE).extend(z))
+    }
+
+    /// Returns the translation these coordinates are drawn at.
+    pub fn translation(&self) -> Vec2 {
+        self.0.truncate() * TILE_SIZE
+    }
+
+    /// Returns the coordinates shown at a position on the screen, on the layer `z`.
+    pub fn from_screen(
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        screen_position: Vec2,
+        z: f32,
+    ) -> Option<Self> {
+        camera
+            .viewport_to_world_2d(camera_transform, screen_position)
+            .ok()
+            .map(|translation| WorldCoordinates::from_translation(translation, z))
+    }
+
+    /// Returns where on the screen these coordinates are shown.
+    pub fn to_screen(&self, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Vec2> {
+        camera
+            .world_to_viewport(camera_transform, self.translation().extend(0.0))
+            .ok()
+    }
+
+    /// Returns the block these coordinates lie in. A block reaches half a block from its center
+    /// in every direction, including the lower border but not the upper one.
+    pub fn block(&self) -> IWorldCoordinates {
+        IWorldCoordinates((self.0 + 0.5).floor().as_ivec3())
+    }
+}
+
+impl From<&IWorldCoordinates> for WorldCoordinates {
+    fn from(wc: &IWorldCoordinates) -> WorldCoordinates {
+        WorldCoordinates(wc.0.as_vec3())
+    }
+}
+
+#[test]
+fn test_world_coordinates_neighbors() {
+    let coordinates = IWorldCoordinates(IVec3::new(5, -9, 2));
+    let neighbors = coordinates.all_neighbors();
+    assert_eq!(neighbors.len(), 26);
+    assert_eq!(
+        neighbors
+            .iter()
+            .map(|(neighbor, cost)| (neighbor.0, *cost))
+            .collect::<Vec<_>>(),
+        coordinates.0.all_neighbors()
+    );
+    for (neighbor, _) in coordinates.neighbors(Connectivity::Faces) {
+        assert_eq!(coordinates.adjacency(&neighbor), Some(Adjacency::Face));
+    }
+}
+
+#[test]
+fn test_chunk_borders() {
+    let inside = IWorldCoordinates(IVec3::new(5, 5, 5));
+    assert!(!inside.is_on_chunk_border());
+    assert!(inside.neighboring_chunks(Connectivity::Corners).is_empty());
+
+    // the corner of a chunk touches seven other chunks, but only three through its faces
+    let corner = IWorldCoordinates(IVec3::new(-16, -16, -16));
+    assert_eq!(corner.chunk(), ChunkCoordinates(IVec3::splat(-1)));
+    assert!(corner.is_on_chunk_border());
+    assert_eq!(corner.neighboring_chunks(Connectivity::Faces).len(), 3);
+    assert_eq!(corner.neighboring_chunks(Connectivity::Edges).len(), 6);
+    assert_eq!(corner.neighboring_chunks(Connectivity::Corners).len(), 7);
+
+    let edge = IWorldCoordinates(IVec3::new(15, 3, 3));
+    assert_eq!(
+        edge.neighboring_chunks(Connectivity::Corners),
+        vec![ChunkCoordinates(IVec3::new(1, 0, 0))]
+    );
+}
+
+/// A small, deterministic random generator, so the round trip tests cover many coordinates.
+#[cfg(test)]
+fn samples() -> impl Iterator<Item = IVec3> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    std::iter::repeat_with(move || {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        let value = |shift: u32| ((state >> shift) as u16 as i32) - 32768;
+        IVec3::new(value(16), value(32), value(48))
+    })
+    .take(10_000)
+    .chain([
+        IVec3::ZERO,
+        IVec3::NEG_ONE,
+        IVec3::splat(-16),
+        IVec3::splat(15),
+    ])
+}
+
+#[test]
+fn test_chunk_round_trip() {
+    for sample in samples() {
+        let coordinates = IWorldCoordinates(sample);
+        let (chunk, block) = coordinates.to_chunk_and_block();
+        assert!(block.0.cmplt(CHUNK_SIZE).all(), "{:?}", coordinates);
+        assert_eq!(chunk.block(block), coordinates);
+        assert!(chunk.origin().0.cmple(sample).all());
+    }
+}
+
+#[test]
+fn test_translation_round_trip() {
+    for sample in samples() {
+        let coordinates = IWorldCoordinates(sample);
+        assert_eq!(coordinates.center().block(), coordinates);
+        let translation = coordinates.center().translation();
+        let back = WorldCoordinates::from_translation(translation, sample.z as f32);
+        assert_eq!(back.block(), coordinates);
+        // anything within the block belongs to it, the upper border belongs to the next one
+        for offset in [
+            Vec3::splat(-0.5),
+            Vec3::splat(0.49),
+            Vec3::new(0.3, -0.2, 0.0),
+        ] {
+            let inside = WorldCoordinates(coordinates.center().0 + offset);
+            assert_eq!(inside.block(), coordinates, "{:?}", inside);
+        }
+        let border = WorldCoordinates(coordinates.center().0 + 0.5);
+        assert_eq!(border.block().0, sample + 1);
+    }
+}
+
+#[test]
+fn test_block_at_half_values() {
+    // halfway between two blocks belongs to the upper one, unlike rounding away from zero
+    for (value, block) in [(-1.5, -1), (-0.5, 0), (0.5, 1), (1.5, 2)] {
+        let coordinates = WorldCoordinates(Vec3::splat(value));
+        assert_eq!(coordinates.block().0, IVec3::splat(block), "{value}");
+    }
+    assert_eq!(
+        WorldCoordinates(Vec3::splat(-0.51)).block().0,
+        IVec3::NEG_ONE
+    );
+}
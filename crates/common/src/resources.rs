@@ -1 +1,32 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+/// The part of the world that can be played in, both corners inclusive. Everything outside is
+/// beyond the map edge.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Reflect, Resource, Serialize)]
+#[reflect(Resource)]
+pub struct MapBounds {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Default for MapBounds {
+    /// Unbounded, the whole world can be played in.
+    fn default() -> Self {
+        MapBounds {
+            min: IVec3::MIN,
+            max: IVec3::MAX,
+        }
+    }
+}
+
+impl MapBounds {
+    pub fn contains(&self, coordinates: IVec3) -> bool {
+        coordinates.cmpge(self.min).all() && coordinates.cmple(self.max).all()
+    }
+
+    /// Returns the closest coordinates within the bounds.
+    pub fn clamp(&self, coordinates: IVec3) -> IVec3 {
+        coordinates.clamp(self.min, self.max)
+    }
+}
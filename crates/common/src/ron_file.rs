@@ -0,0 +1,21 @@
+//! Reading and writing the RON files the game keeps on disk, like save games and presets.
+
+use std::{error::Error, fs, path::Path};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Writes a value to a RON file, creating its directory if needed.
+pub fn write<T: Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn Error>> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Reads a value from a RON file.
+pub fn read<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(ron::from_str(&contents)?)
+}
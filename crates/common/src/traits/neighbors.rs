@@ -14,10 +14,73 @@ pub trait Neighbors<T> {
     /// Returns all neighbors with their squared cost.
     /// Order is like in [`Self::same_layer_neighbors`] but first on the layer above, then the same layer, then the layer below.
     fn all_neighbors(&self) -> Vec<(T, u32)>;
+
+    /// Returns the neighbors that count as connected, in the same order as [`Self::all_neighbors`].
+    fn neighbors(&self, connectivity: Connectivity) -> Vec<(T, u32)> {
+        self.all_neighbors()
+            .into_iter()
+            .filter(|(_, squared_distance)| {
+                Adjacency::from_squared_distance(*squared_distance)
+                    .is_some_and(|adjacency| connectivity.includes(adjacency))
+            })
+            .collect()
+    }
 }
 
 pub type SquaredDistance = u32;
 
+/// How a neighbor touches a block.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Adjacency {
+    /// One step along a single axis.
+    Face,
+    /// One step along two axes.
+    Edge,
+    /// One step along all three axes.
+    Corner,
+}
+
+impl Adjacency {
+    /// Classifies a neighbor by its squared distance, as returned by [`Neighbors`].
+    pub const fn from_squared_distance(squared_distance: SquaredDistance) -> Option<Adjacency> {
+        match squared_distance {
+            1 => Some(Adjacency::Face),
+            2 => Some(Adjacency::Edge),
+            3 => Some(Adjacency::Corner),
+            _ => None,
+        }
+    }
+
+    /// Classifies a neighbor by its offset. Returns none if the offset isn't a neighbor.
+    pub fn from_offset(offset: IVec3) -> Option<Adjacency> {
+        if offset.abs().max_element() != 1 {
+            return None;
+        }
+        Adjacency::from_squared_distance(offset.length_squared() as SquaredDistance)
+    }
+}
+
+/// Which neighbors count as connected.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Connectivity {
+    /// The 6 neighbors sharing a face.
+    Faces,
+    /// The 18 neighbors sharing a face or an edge.
+    Edges,
+    /// All 26 neighbors.
+    Corners,
+}
+
+impl Connectivity {
+    pub const fn includes(&self, adjacency: Adjacency) -> bool {
+        match self {
+            Connectivity::Faces => matches!(adjacency, Adjacency::Face),
+            Connectivity::Edges => !matches!(adjacency, Adjacency::Corner),
+            Connectivity::Corners => true,
+        }
+    }
+}
+
 #[rustfmt::skip]
 impl Neighbors<IVec3> for IVec3 {
     fn same_layer_neighbors(&self) -> Vec<(IVec3, SquaredDistance)> {
@@ -45,3 +108,28 @@ impl Neighbors<IVec3> for IVec3 {
         ]
     }
 }
+
+#[test]
+fn test_connectivity() {
+    let origin = IVec3::new(-3, 7, -20);
+    for (connectivity, count) in [
+        (Connectivity::Faces, 6),
+        (Connectivity::Edges, 18),
+        (Connectivity::Corners, 26),
+    ] {
+        let neighbors = origin.neighbors(connectivity);
+        assert_eq!(neighbors.len(), count, "{:?}", connectivity);
+        for (neighbor, squared_distance) in neighbors {
+            let offset = neighbor - origin;
+            assert_eq!(offset.length_squared() as SquaredDistance, squared_distance);
+            let adjacency = Adjacency::from_offset(offset).unwrap();
+            assert!(connectivity.includes(adjacency));
+        }
+    }
+    assert_eq!(Adjacency::from_offset(IVec3::ZERO), None);
+    assert_eq!(Adjacency::from_offset(IVec3::new(2, 0, 0)), None);
+    assert_eq!(
+        Adjacency::from_offset(IVec3::new(1, -1, 0)),
+        Some(Adjacency::Edge)
+    );
+}
@@ -2,7 +2,8 @@ use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPrimaryContextPass};
 use camera::CameraLayer;
 use common::{
-    functions::world_position_to_world_coordinates, states::AppState, types::IWorldCoordinates,
+    coordinates::{IWorldCoordinates, WorldCoordinates},
+    states::AppState,
 };
 use leafwing_input_manager::{
     Actionlike,
@@ -80,14 +81,11 @@ fn handle_brush_input(
     let action_state = query.into_inner();
     let (camera, camera_transform, layer) = camera.into_inner();
     if action_state.pressed(&MouseControls::PrimaryAction)
-        && let Some(world_position) = window
-            .cursor_position()
-            .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
-            .map(|ray| ray.origin.truncate())
+        && let Some(world_coordinates) = window.cursor_position().and_then(|cursor| {
+            WorldCoordinates::from_screen(camera, camera_transform, cursor, layer.0 as f32)
+        })
     {
-        let world_coordinates =
-            world_position_to_world_coordinates(world_position.extend(layer.0 as f32));
-        brush_event_writer.write(BrushInputEvent::Designated(world_coordinates));
+        brush_event_writer.write(BrushInputEvent::Designated(world_coordinates.block()));
     }
 }
 
@@ -1,7 +1,7 @@
 use animation::{AnimationConfig, AnimationState, Frames};
 use assets::dwarf_sprite::DwarfSpriteAsset;
 use bevy::prelude::*;
-use common::{traits::AddNamedObserver, types::WorldCoordinates};
+use common::{coordinates::WorldCoordinates, traits::AddNamedObserver};
 use work::Worker;
 
 pub fn plugin(app: &mut App) {
@@ -1,8 +1,9 @@
 use assets::icon_asset::IconAsset;
 use bevy::prelude::*;
-use camera::CameraPlugin;
-use common::{states::AppState, traits::AddNamedObserver, types::WorldCoordinates};
+use camera::{CameraLayer, CameraPlugin};
+use common::{coordinates::WorldCoordinates, states::AppState, traits::AddNamedObserver};
 use dwarf::Dwarf;
+use map_generation::map_generation::Embark;
 use work::WorkOrder;
 
 pub fn plugin(app: &mut App) {
@@ -12,16 +13,34 @@ pub fn plugin(app: &mut App) {
         dwarf::plugin,
         map_generation::plugin,
         pathfinding::plugin,
+        save_game::plugin,
         work::plugin,
     ))
-    .add_systems(OnEnter(AppState::MainGame), setup)
+    .add_systems(
+        Update,
+        embark
+            .run_if(resource_exists::<Embark>)
+            .run_if(in_state(AppState::MainGame)),
+    )
     .add_named_observer(add_vis_to_work_order, "add_vis_to_work_order");
 }
 
-fn setup(mut commands: Commands) {
-    for i in 0..1 {
-        commands.spawn((Dwarf, WorldCoordinates(Vec3::new((i - 4) as f32, 0.0, 0.0))));
+/// Spawns the dwarves of a new world at the embark site and moves the camera there.
+fn embark(
+    embark: Res<Embark>,
+    camera: Single<(&mut Transform, &mut CameraLayer), With<Camera2d>>,
+    mut commands: Commands,
+) {
+    let position = embark.0;
+    for _ in 0..1 {
+        commands.spawn((Dwarf, WorldCoordinates::from(&position)));
     }
+    let (mut camera_transform, mut camera_layer) = camera.into_inner();
+    let translation = position.center().translation();
+    camera_transform.translation.x = translation.x;
+    camera_transform.translation.y = translation.y;
+    camera_layer.0 = position.0.z;
+    commands.remove_resource::<Embark>();
 }
 
 fn add_vis_to_work_order(
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block_type::SolidMaterial,
+    vegetation::{Plant, Tree},
+};
+
+/// The climate zone of a column, deciding what grows on it and what its ground is made of.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum Biome {
+    Desert,
+    Grassland,
+    Forest,
+    Swamp,
+    Tundra,
+    Mountains,
+}
+
+/// What happens to surface water within a biome.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum LiquidBehaviour {
+    /// Rivers and lakes stay as they are.
+    Flowing,
+    /// The top layer of water freezes to ice.
+    Frozen,
+    /// Lakes dry out, only rivers and the ocean are left.
+    Evaporating,
+    /// Shallow pools of standing water form on flat ground.
+    Pooling,
+}
+
+/// Parameters of the biome layer.
+///
+/// Temperature and rainfall are noise values, roughly between -0.5 and 0.5. The offsets shift the
+/// whole world towards hotter or wetter biomes.
+#[derive(Clone, Debug, Deserialize, Reflect, Serialize)]
+#[serde(default)]
+pub struct BiomeSettings {
+    /// How many blocks it takes for the climate to change noticeably.
+    pub scale: f64,
+    pub temperature: f64,
+    pub rainfall: f64,
+    /// How much colder it gets per block above the ocean level.
+    pub lapse_rate: f64,
+    /// Everything higher than this many blocks above the ocean level is mountains, unless it is
+    /// cold enough for tundra.
+    pub mountain_height: i32,
+    /// Scales the vegetation density of every biome.
+    pub vegetation: f64,
+}
+
+impl Default for BiomeSettings {
+    fn default() -> Self {
+        BiomeSettings {
+            scale: 256.0,
+            temperature: 0.0,
+            rainfall: 0.0,
+            lapse_rate: 0.003,
+            mountain_height: 35,
+            vegetation: 1.0,
+        }
+    }
+}
+
+impl Biome {
+    /// Picks the biome of a column from its climate and its height above the ocean level.
+    pub fn classify(
+        temperature: f64,
+        rainfall: f64,
+        height: i32,
+        settings: &BiomeSettings,
+    ) -> Self {
+        if temperature < -0.3 {
+            Biome::Tundra
+        } else if height > settings.mountain_height {
+            Biome::Mountains
+        } else if temperature > 0.15 && rainfall < -0.1 {
+            Biome::Desert
+        } else if rainfall > 0.2 && temperature > -0.1 {
+            Biome::Swamp
+        } else if rainfall > 0.0 {
+            Biome::Forest
+        } else {
+            Biome::Grassland
+        }
+    }
+
+    /// Returns the material of the topmost block. Bare rock is used where there is none.
+    pub const fn surface_material(&self) -> Option<SolidMaterial> {
+        match self {
+            Biome::Desert => Some(SolidMaterial::Sand),
+            Biome::Grassland | Biome::Forest => Some(SolidMaterial::Grass),
+            Biome::Swamp => Some(SolidMaterial::Mud),
+            Biome::Tundra => Some(SolidMaterial::Snow),
+            Biome::Mountains => None,
+        }
+    }
+
+    /// Returns the material of the soil stratum.
+    pub const fn soil_material(&self) -> SolidMaterial {
+        match self {
+            Biome::Desert => SolidMaterial::Sand,
+            Biome::Swamp => SolidMaterial::Mud,
+            _ => SolidMaterial::Dirt,
+        }
+    }
+
+    /// Returns how thick the soil is, relative to the average.
+    pub const fn soil_depth(&self) -> f64 {
+        match self {
+            Biome::Desert | Biome::Swamp => 1.5,
+            Biome::Grassland | Biome::Forest => 1.0,
+            Biome::Tundra => 0.5,
+            Biome::Mountains => 0.0,
+        }
+    }
+
+    pub const fn liquid_behaviour(&self) -> LiquidBehaviour {
+        match self {
+            Biome::Desert => LiquidBehaviour::Evaporating,
+            Biome::Swamp => LiquidBehaviour::Pooling,
+            Biome::Tundra => LiquidBehaviour::Frozen,
+            Biome::Grassland | Biome::Forest | Biome::Mountains => LiquidBehaviour::Flowing,
+        }
+    }
+
+    /// Returns the share of surface blocks that are covered with plants.
+    pub const fn vegetation_density(&self) -> f64 {
+        match self {
+            Biome::Desert => 0.01,
+            Biome::Tundra | Biome::Mountains => 0.02,
+            Biome::Grassland => 0.05,
+            Biome::Swamp => 0.15,
+            Biome::Forest => 0.2,
+        }
+    }
+
+    /// Returns the share of vegetation that are trees. The rest is split between shrubs and plants.
+    pub const fn tree_share(&self) -> f64 {
+        match self {
+            Biome::Forest => 0.5,
+            Biome::Swamp | Biome::Tundra | Biome::Mountains => 0.3,
+            Biome::Grassland | Biome::Desert => 0.1,
+        }
+    }
+
+    pub const fn tree(&self) -> Tree {
+        match self {
+            Biome::Grassland | Biome::Forest => Tree::Oak,
+            Biome::Tundra | Biome::Mountains => Tree::Pine,
+            Biome::Swamp => Tree::Willow,
+            Biome::Desert => Tree::Palm,
+        }
+    }
+
+    pub const fn plant(&self) -> Plant {
+        match self {
+            Biome::Forest => Plant::Berries,
+            Biome::Grassland | Biome::Tundra | Biome::Mountains => Plant::Herbs,
+            Biome::Swamp => Plant::Reeds,
+            Biome::Desert => Plant::Cactus,
+        }
+    }
+}
+
+#[test]
+fn test_classify() {
+    let settings = BiomeSettings::default();
+    let classify = |temperature, rainfall| Biome::classify(temperature, rainfall, 0, &settings);
+    assert_eq!(classify(-0.4, -0.3), Biome::Tundra);
+    assert_eq!(classify(-0.4, 0.4), Biome::Tundra);
+    assert_eq!(classify(0.3, -0.3), Biome::Desert);
+    assert_eq!(classify(0.3, 0.3), Biome::Swamp);
+    assert_eq!(classify(0.0, 0.1), Biome::Forest);
+    assert_eq!(classify(0.0, -0.3), Biome::Grassland);
+    // too cold for swamps and deserts
+    assert_eq!(classify(-0.2, 0.3), Biome::Forest);
+    assert_eq!(classify(0.1, -0.3), Biome::Grassland);
+
+    // high ground is mountains, unless it is cold enough for tundra
+    let height = settings.mountain_height + 1;
+    assert_eq!(
+        Biome::classify(0.3, -0.3, height, &settings),
+        Biome::Mountains
+    );
+    assert_eq!(Biome::classify(-0.4, 0.0, height, &settings), Biome::Tundra);
+}
@@ -5,44 +5,185 @@ use bevy_ecs_tilemap::{
     map::TilemapId,
     tiles::{TileBundle, TileColor, TileFlip, TilePos, TileStorage, TileTextureIndex},
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+use crate::{passage::Passage, vegetation::Vegetation};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
 pub enum BlockType {
     Solid(SolidMaterial),
     Liquid,
+    Vegetation(Vegetation),
+    Passage(Passage),
     None,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
 pub enum SolidMaterial {
     Dirt,
     Grass,
+    Sand,
+    Mud,
+    Snow,
+    Ice,
+    // sedimentary
+    Sandstone,
+    Limestone,
+    // igneous
+    Granite,
+    Basalt,
+    // metamorphic
+    Marble,
+    Slate,
+    // ores
+    Coal,
+    IronOre,
+    CopperOre,
+    GoldOre,
+    // gems
+    Amethyst,
+    Emerald,
+    Diamond,
+}
+
+/// The geological layers of the world, from the surface downwards.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum Stratum {
+    Soil,
+    Sedimentary,
+    Igneous,
+    Metamorphic,
 }
 
 impl SolidMaterial {
-    fn color(&self) -> Color {
+    pub(crate) fn color(&self) -> Color {
         match self {
             SolidMaterial::Dirt | SolidMaterial::Grass => Color::srgb_u8(223, 157, 117),
+            SolidMaterial::Sand => Color::srgb_u8(237, 214, 160),
+            SolidMaterial::Mud => Color::srgb_u8(120, 95, 70),
+            SolidMaterial::Snow => Color::srgb_u8(245, 248, 252),
+            SolidMaterial::Ice => Color::srgb_u8(185, 225, 245),
+            SolidMaterial::Sandstone => Color::srgb_u8(214, 186, 136),
+            SolidMaterial::Limestone => Color::srgb_u8(200, 196, 180),
+            SolidMaterial::Granite => Color::srgb_u8(165, 140, 140),
+            SolidMaterial::Basalt => Color::srgb_u8(85, 85, 95),
+            SolidMaterial::Marble => Color::srgb_u8(230, 230, 235),
+            SolidMaterial::Slate => Color::srgb_u8(100, 110, 125),
+            SolidMaterial::Coal => Color::srgb_u8(45, 45, 50),
+            SolidMaterial::IronOre => Color::srgb_u8(170, 105, 85),
+            SolidMaterial::CopperOre => Color::srgb_u8(205, 125, 65),
+            SolidMaterial::GoldOre => Color::srgb_u8(240, 200, 60),
+            SolidMaterial::Amethyst => Color::srgb_u8(155, 90, 200),
+            SolidMaterial::Emerald => Color::srgb_u8(60, 190, 110),
+            SolidMaterial::Diamond => Color::srgb_u8(190, 240, 250),
         }
     }
 
     fn floor_color(&self) -> Color {
         match self {
-            SolidMaterial::Dirt => self.color(),
-            _ => WHITE.into(),
+            SolidMaterial::Grass => WHITE.into(),
+            _ => self.color(),
         }
     }
 
     fn tile_texture_index(&self) -> TileTextureIndex {
         match self {
-            SolidMaterial::Dirt => TileTextureIndex(0),
             SolidMaterial::Grass => TileTextureIndex(1),
+            // stone floors share the dirt texture and are told apart by their color
+            SolidMaterial::Dirt
+            | SolidMaterial::Sand
+            | SolidMaterial::Mud
+            | SolidMaterial::Snow
+            | SolidMaterial::Ice
+            | SolidMaterial::Sandstone
+            | SolidMaterial::Limestone
+            | SolidMaterial::Granite
+            | SolidMaterial::Basalt
+            | SolidMaterial::Marble
+            | SolidMaterial::Slate
+            | SolidMaterial::Coal
+            | SolidMaterial::IronOre
+            | SolidMaterial::CopperOre
+            | SolidMaterial::GoldOre
+            | SolidMaterial::Amethyst
+            | SolidMaterial::Emerald
+            | SolidMaterial::Diamond => TileTextureIndex(0),
         }
     }
 
+    /// Returns the layer this material makes up. Ores and gems are found in several layers and
+    /// don't make up any of them, neither does ice.
+    pub const fn stratum(&self) -> Option<Stratum> {
+        match self {
+            SolidMaterial::Dirt
+            | SolidMaterial::Grass
+            | SolidMaterial::Sand
+            | SolidMaterial::Mud
+            | SolidMaterial::Snow => Some(Stratum::Soil),
+            SolidMaterial::Sandstone | SolidMaterial::Limestone => Some(Stratum::Sedimentary),
+            SolidMaterial::Granite | SolidMaterial::Basalt => Some(Stratum::Igneous),
+            SolidMaterial::Marble | SolidMaterial::Slate => Some(Stratum::Metamorphic),
+            _ => None,
+        }
+    }
+
+    /// Returns true for ores and gems, which are worth more than the rock they are found in.
+    pub const fn is_deposit(&self) -> bool {
+        matches!(
+            self,
+            SolidMaterial::Coal
+                | SolidMaterial::IronOre
+                | SolidMaterial::CopperOre
+                | SolidMaterial::GoldOre
+                | SolidMaterial::Amethyst
+                | SolidMaterial::Emerald
+                | SolidMaterial::Diamond
+        )
+    }
+
+    /// Returns how hard it is to dig through this material, relative to dirt.
+    pub const fn hardness(&self) -> f32 {
+        match self {
+            SolidMaterial::Dirt | SolidMaterial::Grass => 1.0,
+            SolidMaterial::Sand | SolidMaterial::Mud | SolidMaterial::Snow => 0.5,
+            SolidMaterial::Ice => 1.5,
+            SolidMaterial::Sandstone => 2.0,
+            SolidMaterial::Limestone => 2.5,
+            SolidMaterial::Slate => 3.0,
+            SolidMaterial::Marble => 3.5,
+            SolidMaterial::Basalt => 4.5,
+            SolidMaterial::Granite => 5.0,
+            SolidMaterial::Coal => 2.0,
+            SolidMaterial::GoldOre => 3.0,
+            SolidMaterial::CopperOre => 3.5,
+            SolidMaterial::IronOre => 4.0,
+            SolidMaterial::Amethyst => 6.0,
+            SolidMaterial::Emerald => 7.0,
+            SolidMaterial::Diamond => 10.0,
+        }
+    }
+
+    /// Returns how much damage a block of this material takes before it is destroyed.
+    /// A block of dirt takes 1.0.
+    pub const fn durability(&self) -> f32 {
+        self.hardness()
+    }
+
     /// Returns the cost of an entity leaving this field.
     pub const fn traversal_cost(&self) -> f32 {
-        1.0
+        match self {
+            SolidMaterial::Dirt | SolidMaterial::Grass | SolidMaterial::Marble => 1.0,
+            SolidMaterial::Sand | SolidMaterial::Snow => 1.3,
+            SolidMaterial::Mud | SolidMaterial::Ice => 1.5,
+            SolidMaterial::Sandstone | SolidMaterial::Limestone | SolidMaterial::Slate => 1.1,
+            SolidMaterial::Granite => 1.2,
+            SolidMaterial::Basalt => 1.3,
+            SolidMaterial::Coal
+            | SolidMaterial::IronOre
+            | SolidMaterial::CopperOre
+            | SolidMaterial::GoldOre => 1.2,
+            SolidMaterial::Amethyst | SolidMaterial::Emerald | SolidMaterial::Diamond => 1.1,
+        }
     }
 }
 
@@ -51,6 +192,43 @@ impl BlockType {
         matches!(self, BlockType::Solid(_))
     }
 
+    /// Returns true if entities can move through this block. Only empty blocks, passages and
+    /// vegetation other than tree trunks can be passed.
+    pub fn is_passable(&self) -> bool {
+        match self {
+            BlockType::None | BlockType::Passage(_) => true,
+            BlockType::Vegetation(vegetation) => !matches!(vegetation, Vegetation::Trunk(_)),
+            BlockType::Solid(_) | BlockType::Liquid => false,
+        }
+    }
+
+    /// Returns the material of solid blocks.
+    pub fn material(&self) -> Option<SolidMaterial> {
+        match self {
+            BlockType::Solid(material) => Some(*material),
+            _ => None,
+        }
+    }
+
+    /// Returns the passage of blocks that connect layers.
+    pub fn passage(&self) -> Option<Passage> {
+        match self {
+            BlockType::Passage(passage) => Some(*passage),
+            _ => None,
+        }
+    }
+
+    /// Returns how much damage the block takes before it is destroyed. Liquids and empty blocks
+    /// can't be damaged.
+    pub fn durability(&self) -> Option<f32> {
+        match self {
+            BlockType::Solid(material) => Some(material.durability()),
+            BlockType::Vegetation(vegetation) => Some(vegetation.durability()),
+            BlockType::Passage(passage) => Some(passage.durability()),
+            BlockType::Liquid | BlockType::None => None,
+        }
+    }
+
     pub(crate) fn spawn_half_tile(
         &self,
         parent: &mut RelatedSpawnerCommands<'_, ChildOf>,
@@ -98,6 +276,8 @@ impl BlockType {
     fn floor_color(&self) -> Color {
         match self {
             BlockType::Solid(material) => material.floor_color(),
+            BlockType::Vegetation(vegetation) => vegetation.color(),
+            BlockType::Passage(passage) => passage.color(),
             _ => WHITE.into(),
         }
     }
@@ -105,7 +285,11 @@ impl BlockType {
     fn tile_texture_index(&self) -> TileTextureIndex {
         match self {
             BlockType::Solid(material) => material.tile_texture_index(),
-            _ => panic!("tile_texture_index should only be called on BlockType::Solid"),
+            BlockType::Vegetation(vegetation) => vegetation.tile_texture_index(),
+            BlockType::Passage(passage) => passage.tile_texture_index(),
+            _ => panic!(
+                "tile_texture_index should only be called on BlockType::Solid, BlockType::Vegetation or BlockType::Passage"
+            ),
         }
     }
 
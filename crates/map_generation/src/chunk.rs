@@ -1,36 +1,73 @@
-use bevy::prelude::*;
-use common::types::{ChunkBlockCoordinates, ChunkCoordinates, IWorldCoordinates};
-use noise::{NoiseFn, OpenSimplex};
+use std::collections::HashMap;
 
-use crate::block_type::{BlockType, SolidMaterial};
+use bevy::prelude::*;
+pub(crate) use common::constants::CHUNK_SIZE;
+use common::coordinates::{ChunkBlockCoordinates, ChunkCoordinates};
+use serde::{Deserialize, Serialize};
 
-pub(crate) const CHUNK_SIZE: UVec3 = UVec3::new(16, 16, 16);
+use crate::{
+    biome::LiquidBehaviour,
+    block_type::{BlockType, SolidMaterial},
+    terrain::Terrain,
+    vegetation::{MAX_CANOPY_RADIUS, MAX_TREE_HEIGHT},
+};
 
-#[derive(Reflect)]
+#[derive(Clone, Reflect)]
 pub(crate) struct Chunk {
     pub(crate) coordinates: ChunkCoordinates,
     pub(crate) blocks: [BlockType; (CHUNK_SIZE.x * CHUNK_SIZE.y * CHUNK_SIZE.z) as usize],
 }
 
+/// All blocks of a chunk that differ from what [`Chunk::new`] generates, keyed by their index.
+///
+/// As long as the delta is kept, the chunk itself can be dropped and regenerated at any time.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Reflect, Serialize)]
+pub(crate) struct ChunkDelta(HashMap<usize, BlockType>);
+
+impl ChunkDelta {
+    pub(crate) fn set(&mut self, block_coordinates: ChunkBlockCoordinates, block: BlockType) {
+        self.0.insert(to_index(block_coordinates), block);
+    }
+}
+
 impl Chunk {
-    pub(crate) fn new(coordinates: ChunkCoordinates, noise: OpenSimplex) -> Self {
+    pub(crate) fn new(coordinates: ChunkCoordinates, terrain: &Terrain) -> Self {
         let mut blocks = [BlockType::None; (CHUNK_SIZE.x * CHUNK_SIZE.y * CHUNK_SIZE.z) as usize];
+        let origin = coordinates.origin().0;
+        // trees growing next to the chunk can reach into it
+        let margin = IVec2::splat(MAX_CANOPY_RADIUS);
+        let size = CHUNK_SIZE.truncate().as_ivec2() + margin * 2;
+        let columns = terrain.columns(origin.truncate() - margin, size);
         for x in 0..CHUNK_SIZE.x {
             for y in 0..CHUNK_SIZE.y {
-                let world_x = coordinates.0.x as f32 + (x as f32 / CHUNK_SIZE.x as f32);
-                let world_y = coordinates.0.y as f32 + (y as f32 / CHUNK_SIZE.y as f32);
-                let threshold = noise
-                    .get([world_x as f64, world_y as f64])
-                    // .remap(-1.0, 1.0, -10984.0, 8848.0)
-                    .remap(-1.0, 1.0, -100.0, 100.0)
-                    .round() as i32;
+                let column =
+                    &columns[((x as i32 + margin.x) * size.y + y as i32 + margin.y) as usize];
+                let threshold = column.surface;
+                let water_level = column.water_level.unwrap_or(threshold);
                 for z in 0..CHUNK_SIZE.z {
-                    let height = coordinates.0.z * CHUNK_SIZE.z as i32 + z as i32;
-                    let tile_type = if height == threshold && threshold > 0 {
-                        BlockType::Solid(SolidMaterial::Grass)
-                    } else if height < threshold {
-                        BlockType::Solid(SolidMaterial::Dirt)
-                    } else if height > threshold && height < 0 {
+                    let position = coordinates.block((x, y, z)).0;
+                    let height = position.z;
+                    let tile_type = if height == threshold && column.water_level.is_none() {
+                        BlockType::Solid(
+                            column
+                                .biome
+                                .surface_material()
+                                .unwrap_or_else(|| terrain.material(position, 0, column.biome)),
+                        )
+                    } else if height <= threshold {
+                        let depth = threshold - height;
+                        if !terrain.is_hollow(position, depth) {
+                            BlockType::Solid(terrain.material(position, depth, column.biome))
+                        } else if terrain.is_aquifer(position, depth, column.biome) {
+                            BlockType::Liquid
+                        } else {
+                            BlockType::None
+                        }
+                    } else if height + 1 == water_level
+                        && column.biome.liquid_behaviour() == LiquidBehaviour::Frozen
+                    {
+                        BlockType::Solid(SolidMaterial::Ice)
+                    } else if height < water_level {
                         BlockType::Liquid
                     } else {
                         BlockType::None
@@ -39,28 +76,44 @@ impl Chunk {
                 }
             }
         }
+        for column in &columns {
+            if column.surface + MAX_TREE_HEIGHT < origin.z
+                || column.surface >= origin.z + CHUNK_SIZE.z as i32
+            {
+                continue;
+            }
+            for (position, vegetation) in terrain.vegetation(column) {
+                let local = position - origin;
+                if local.cmpge(IVec3::ZERO).all() && local.cmplt(CHUNK_SIZE.as_ivec3()).all() {
+                    let index = to_index(ChunkBlockCoordinates(local.as_uvec3()));
+                    if blocks[index] == BlockType::None {
+                        blocks[index] = BlockType::Vegetation(vegetation);
+                    }
+                }
+            }
+        }
         Chunk {
             coordinates,
             blocks,
         }
     }
 
-    pub(crate) fn remove_block(&mut self, block_coordinates: ChunkBlockCoordinates) {
-        let index = to_index(block_coordinates);
-        self.blocks[index] = BlockType::None;
+    /// Generates a chunk and applies the given modifications on top of it.
+    pub(crate) fn with_delta(
+        coordinates: ChunkCoordinates,
+        terrain: &Terrain,
+        delta: Option<&ChunkDelta>,
+    ) -> Self {
+        let mut chunk = Chunk::new(coordinates, terrain);
+        for (index, block) in delta.iter().flat_map(|delta| delta.0.iter()) {
+            chunk.blocks[*index] = *block;
+        }
+        chunk
     }
-}
-
-pub(crate) trait ToChunkAndBlock {
-    fn to_chunk_and_block(&self) -> (ChunkCoordinates, ChunkBlockCoordinates);
-}
 
-impl ToChunkAndBlock for IWorldCoordinates {
-    fn to_chunk_and_block(&self) -> (ChunkCoordinates, ChunkBlockCoordinates) {
-        (
-            ChunkCoordinates(self.0.div_euclid(CHUNK_SIZE.as_ivec3())),
-            ChunkBlockCoordinates(self.0.rem_euclid(CHUNK_SIZE.as_ivec3()).as_uvec3()),
-        )
+    pub(crate) fn set_block(&mut self, block_coordinates: ChunkBlockCoordinates, block: BlockType) {
+        let index = to_index(block_coordinates);
+        self.blocks[index] = block;
     }
 }
 
@@ -72,17 +125,6 @@ pub(crate) fn to_index(coordinates: impl Into<ChunkBlockCoordinates>) -> usize {
         + block_coordinates.0.z) as usize
 }
 
-pub(crate) fn to_world_coordinates(
-    chunk_coordinates: ChunkCoordinates,
-    block_coordinates: impl Into<ChunkBlockCoordinates>,
-) -> IWorldCoordinates {
-    let block_coordinates: ChunkBlockCoordinates = block_coordinates.into();
-    let x = chunk_coordinates.0.x * CHUNK_SIZE.x as i32 + block_coordinates.0.x as i32;
-    let y = chunk_coordinates.0.y * CHUNK_SIZE.y as i32 + block_coordinates.0.y as i32;
-    let z = chunk_coordinates.0.z * CHUNK_SIZE.z as i32 + block_coordinates.0.z as i32;
-    IWorldCoordinates(IVec3::new(x, y, z))
-}
-
 #[test]
 fn test_to_index() {
     let mut index = 0;
@@ -99,8 +141,59 @@ fn test_to_index() {
 #[test]
 fn test_to_world() {
     let chunk_coordinates = ChunkCoordinates(IVec3::ZERO);
+    assert_eq!(chunk_coordinates.block((1, 2, 3)).0, IVec3::new(1, 2, 3))
+}
+
+#[test]
+fn test_with_delta() {
+    let mut delta = ChunkDelta::default();
+    delta.set((1, 2, 3).into(), BlockType::Liquid);
+    let chunk = Chunk::with_delta(
+        ChunkCoordinates(IVec3::ZERO),
+        &Terrain::new(&crate::map_generation::WorldGenerationSettings::default()),
+        Some(&delta),
+    );
+    assert_eq!(chunk.blocks[to_index((1, 2, 3))], BlockType::Liquid);
+}
+
+#[test]
+fn test_tundra_surface() {
+    use common::coordinates::IWorldCoordinates;
+
+    use crate::{biome::BiomeSettings, map_generation::WorldGenerationSettings};
+
+    let terrain = Terrain::new(&WorldGenerationSettings {
+        biomes: BiomeSettings {
+            temperature: -1.0,
+            ..default()
+        },
+        ..default()
+    });
+    let columns = terrain.columns(IVec2::splat(-256), IVec2::splat(512));
+    let block_at = |column: IVec2, z: i32| {
+        let (chunk, block) = IWorldCoordinates(column.extend(z)).to_chunk_and_block();
+        Chunk::new(chunk, &terrain).blocks[to_index(block)]
+    };
+
+    // water freezes over
+    let wet = columns
+        .iter()
+        .find(|column| column.water_level.is_some())
+        .unwrap();
+    let level = wet.water_level.unwrap();
+    assert_eq!(
+        block_at(wet.coordinates, level - 1),
+        BlockType::Solid(SolidMaterial::Ice)
+    );
+    assert_eq!(block_at(wet.coordinates, level), BlockType::None);
+
+    // dry ground is covered in snow
+    let dry = columns
+        .iter()
+        .find(|column| column.water_level.is_none())
+        .unwrap();
     assert_eq!(
-        to_world_coordinates(chunk_coordinates, (1, 2, 3)),
-        IWorldCoordinates(IVec3::new(1, 2, 3))
-    )
+        block_at(dry.coordinates, dry.surface),
+        BlockType::Solid(SolidMaterial::Snow)
+    );
 }
@@ -1,7 +1,5 @@
 use bevy::prelude::*;
-use common::{constants::TILE_SIZE, types::ChunkCoordinates};
-
-use crate::chunk::CHUNK_SIZE;
+use common::coordinates::ChunkCoordinates;
 
 mod plugin;
 mod types;
@@ -17,18 +15,14 @@ pub(crate) struct ChunkVisualisationSettings {
 /// storage. The visible chunks however are spawned in via ChunkVisualisations.
 #[derive(Component, Reflect)]
 #[reflect(Component)]
-pub(crate) struct ChunkVisualisation(ChunkCoordinates);
+pub(crate) struct ChunkVisualisation(pub(crate) ChunkCoordinates);
 
 impl ChunkVisualisation {
     fn bundle(coordinates: ChunkCoordinates) -> impl Bundle {
         (
             Name::new(format!("Chunk {}", coordinates.0)),
             ChunkVisualisation(coordinates),
-            Transform::from_xyz(
-                coordinates.0.x as f32 * CHUNK_SIZE.x as f32 * TILE_SIZE.x,
-                coordinates.0.y as f32 * CHUNK_SIZE.y as f32 * TILE_SIZE.y,
-                0.0,
-            ),
+            Transform::from_translation(coordinates.origin().center().translation().extend(0.0)),
             Visibility::Inherited,
         )
     }
@@ -6,13 +6,14 @@ use bevy_ecs_tilemap::prelude::*;
 use camera::CameraLayer;
 use common::{
     constants::TILE_SIZE,
+    coordinates::{ChunkCoordinates, IWorldCoordinates},
+    resources::MapBounds,
     states::AppState,
-    traits::{AddNamedObserver, Neighbors},
-    types::ChunkCoordinates,
+    traits::{AddNamedObserver, Connectivity},
 };
 
 use crate::{
-    chunk::{CHUNK_SIZE, ToChunkAndBlock, to_world_coordinates},
+    chunk::CHUNK_SIZE,
     chunk_visualisation::{
         ChunkVisualisation, ChunkVisualisationSettings,
         types::{TileType, Tilemap, Tilemaps, ToTiles},
@@ -60,10 +61,16 @@ pub(crate) fn on_insert(
     for x in 0..CHUNK_SIZE.x {
         for y in 0..CHUNK_SIZE.y {
             for z in 0..=chunk_visualisation_settings.visible_layers {
-                let world_coordinates = to_world_coordinates(chunk_visualisation.0, (x, y, 0))
+                let world_coordinates = chunk_visualisation
+                    .0
+                    .block((x, y, 0))
                     // we begin at the camera layer. If we don't find a block, we step down a layer until we either find one
                     // or the opacity of the fog is too high to see
                     .with_z_offset(camera_layer.0 - z);
+                // nothing is drawn beyond the map edge
+                if !world_map.bounds().contains(world_coordinates.0) {
+                    break;
+                }
                 if let Some(block) = world_map.get_block(world_coordinates)
                     && block.to_tile(
                         UVec2::new(x, y),
@@ -86,6 +93,7 @@ pub(crate) fn on_insert(
                 TileType::Full => &tileset.floor_tileset,
                 TileType::Half => &tileset.soil_tileset,
                 TileType::Animated => &tileset.water_tileset,
+                TileType::Vegetation => &tileset.floor_tileset,
                 TileType::Fog => &tileset.fog_tileset,
             };
             spawn_tile_map(&mut commands, tile_type, map, target, tileset_image);
@@ -140,32 +148,22 @@ pub(crate) fn update(
 ) {
     for block_update in message_reader.read() {
         match block_update {
-            BlockUpdate::Added => todo!(),
-            BlockUpdate::Removed(world_coordinates) => {
-                let (chunk_coordinates, block_coordinates) = world_coordinates.to_chunk_and_block();
-
-                let mut all = vec![chunk_coordinates];
-                if block_coordinates.0.x == 0
-                    || block_coordinates.0.y == 0
-                    || block_coordinates.0.x == CHUNK_SIZE.x - 1
-                    || block_coordinates.0.y == CHUNK_SIZE.y - 1
-                {
-                    let neighbors: Vec<ChunkCoordinates> = chunk_coordinates
-                        .0
-                        .same_layer_neighbors()
-                        .iter()
-                        .map(|(coordinate, _)| ChunkCoordinates(*coordinate))
-                        .collect();
-                    all.extend(neighbors);
+            // neighbouring tiles change their shape as well, so chunks next to the block are
+            // redrawn if it lies on the border
+            BlockUpdate::Added(world_coordinates) | BlockUpdate::Removed(world_coordinates) => {
+                let mut columns = vec![world_coordinates.chunk().0.truncate()];
+                for chunk in world_coordinates.neighboring_chunks(Connectivity::Corners) {
+                    if !columns.contains(&chunk.0.truncate()) {
+                        columns.push(chunk.0.truncate());
+                    }
                 }
-                for coordinates in all {
-                    if let Some((entity, _)) = query
-                        .iter()
-                        .find(|(_, chunk_vis)| chunk_vis.0 == coordinates)
-                    {
+                // chunk visualisations are drawn from the camera layer downwards, so their z
+                // coordinate is the layer and only x and y have to match
+                for (entity, chunk_vis) in &query {
+                    if columns.contains(&chunk_vis.0.0.truncate()) {
                         commands
                             .entity(entity)
-                            .insert(ChunkVisualisation(coordinates));
+                            .insert(ChunkVisualisation(chunk_vis.0));
                     }
                 }
             }
@@ -176,11 +174,12 @@ pub(crate) fn update(
 
 fn request(
     camera_transform: Single<(&Transform, &CameraLayer, &Projection)>,
+    world_map: Res<WorldMap>,
     chunks: Query<&ChunkVisualisation>,
     mut commands: Commands,
 ) {
     let Some((x_range, y_range, z_range)) =
-        calculate_visible_chunk_ranges_from_single(camera_transform)
+        calculate_visible_chunk_ranges_from_single(camera_transform, world_map.bounds())
     else {
         return;
     };
@@ -204,11 +203,12 @@ fn request(
 
 fn delete(
     camera_transform: Single<(&Transform, &CameraLayer, &Projection)>,
+    world_map: Res<WorldMap>,
     chunks: Query<(Entity, &ChunkVisualisation)>,
     mut commands: Commands,
 ) {
     let Some((x_range, y_range, z_range)) =
-        calculate_visible_chunk_ranges_from_single(camera_transform)
+        calculate_visible_chunk_ranges_from_single(camera_transform, world_map.bounds())
     else {
         return;
     };
@@ -224,14 +224,23 @@ fn delete(
     }
 }
 
+/// Calculates which chunks are currently visible and within the map.
 fn calculate_visible_chunk_ranges_from_single(
     camera_transform: Single<(&Transform, &CameraLayer, &Projection)>,
+    bounds: MapBounds,
 ) -> Option<(Range<i32>, Range<i32>, RangeInclusive<i32>)> {
     let (transform, layer, projection) = camera_transform.into_inner();
     let Projection::Orthographic(values) = projection else {
         return None;
     };
-    Some(calculate_visible_chunk_ranges(transform, layer, values))
+    let (x_range, y_range, z_range) = calculate_visible_chunk_ranges(transform, layer, values);
+    let (min, _) = IWorldCoordinates(bounds.min).to_chunk_and_block();
+    let (max, _) = IWorldCoordinates(bounds.max).to_chunk_and_block();
+    Some((
+        x_range.start.max(min.0.x)..x_range.end.min(max.0.x + 1),
+        y_range.start.max(min.0.y)..y_range.end.min(max.0.y + 1),
+        *z_range.start().max(&min.0.z)..=*z_range.end().min(&max.0.z),
+    ))
 }
 
 /// Calculates which chunks are currently visible
@@ -2,7 +2,7 @@ use std::{collections::HashMap, hash::Hash};
 
 use bevy::{color::palettes::css::WHITE, ecs::relationship::RelatedSpawnerCommands, prelude::*};
 use bevy_ecs_tilemap::prelude::*;
-use common::{constants::TILE_SIZE, traits::Neighbors, types::IWorldCoordinates};
+use common::{constants::TILE_SIZE, coordinates::IWorldCoordinates, traits::Neighbors};
 
 use crate::{block_type::BlockType, chunk::CHUNK_SIZE, world_map::WorldMap};
 
@@ -11,6 +11,7 @@ pub(crate) enum TileType {
     Full,
     Half,
     Animated,
+    Vegetation,
     Fog,
 }
 
@@ -37,6 +38,7 @@ impl TileType {
             TileType::Full => "Half-Tile Tilemap",
             TileType::Half => "Full-Tile Tilemap",
             TileType::Animated => "Animated Tilemap",
+            TileType::Vegetation => "Vegetation Tilemap",
             TileType::Fog => "Fog Tilemap",
         }
     }
@@ -47,6 +49,7 @@ impl TileType {
             TileType::Full => -0.5,
             TileType::Half => 0.0,
             TileType::Animated => -0.5,
+            TileType::Vegetation => -0.25,
             TileType::Fog => 1.0,
         }
     }
@@ -141,6 +144,7 @@ impl Default for Tilemaps {
         map.insert(TileType::Full, HashMap::new());
         map.insert(TileType::Half, HashMap::new());
         map.insert(TileType::Animated, HashMap::new());
+        map.insert(TileType::Vegetation, HashMap::new());
         map.insert(TileType::Fog, HashMap::new());
         Tilemaps(map)
     }
@@ -211,6 +215,31 @@ impl ToTiles for BlockType {
                 });
                 true
             }
+            BlockType::Vegetation(vegetation) if vegetation.is_opaque() => {
+                tilemaps.0.entry(TileType::Full).and_modify(|m| {
+                    m.insert(
+                        TilePosType::Full(TilePos::new(pos.x, pos.y)),
+                        TileWrapper::Full(*self),
+                    );
+                });
+                true
+            }
+            BlockType::Vegetation(_) | BlockType::Passage(_) => {
+                // shrubs, plants and passages are drawn on top of the ground below them
+                tilemaps.0.entry(TileType::Vegetation).and_modify(|m| {
+                    m.entry(TilePosType::Full(TilePos::new(pos.x, pos.y)))
+                        .or_insert(TileWrapper::Full(*self));
+                });
+                if z != 0 {
+                    tilemaps.0.entry(TileType::Fog).and_modify(|m| {
+                        m.insert(
+                            TilePosType::Full(TilePos::new(pos.x, pos.y)),
+                            TileWrapper::Fog(z as f32 / visible_layers),
+                        );
+                    });
+                }
+                false
+            }
             BlockType::None if z != 0 => {
                 tilemaps.0.entry(TileType::Fog).and_modify(|m| {
                     m.insert(
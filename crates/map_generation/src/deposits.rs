@@ -0,0 +1,125 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use noise::{NoiseFn, OpenSimplex};
+
+use crate::block_type::{SolidMaterial, Stratum};
+
+/// The shape a deposit is generated in.
+enum Shape {
+    /// Long, thin tubes winding through the rock, `scale` blocks between bends.
+    Vein { scale: f64 },
+    /// Small blobs, roughly `scale` blocks across.
+    Cluster { scale: f64 },
+}
+
+/// Describes where and how often a material is placed inside the stone layers.
+pub(crate) struct Deposit {
+    pub(crate) material: SolidMaterial,
+    shape: Shape,
+    /// The layers this deposit can be found in.
+    host: &'static [Stratum],
+    /// How many blocks below the surface this deposit can be found.
+    depth: Range<i32>,
+    /// Roughly the share of host rock that is replaced by this deposit, between 0.0 and 1.0.
+    frequency: f64,
+}
+
+/// Every deposit that is generated, checked in order. Rarer deposits come first, so they
+/// aren't hidden by more common ones.
+pub(crate) const DEPOSITS: [Deposit; 7] = [
+    Deposit {
+        material: SolidMaterial::Diamond,
+        shape: Shape::Cluster { scale: 2.0 },
+        host: &[Stratum::Metamorphic],
+        depth: 100..i32::MAX,
+        frequency: 0.002,
+    },
+    Deposit {
+        material: SolidMaterial::Emerald,
+        shape: Shape::Cluster { scale: 2.5 },
+        host: &[Stratum::Metamorphic],
+        depth: 50..i32::MAX,
+        frequency: 0.004,
+    },
+    Deposit {
+        material: SolidMaterial::Amethyst,
+        shape: Shape::Cluster { scale: 3.0 },
+        host: &[Stratum::Igneous],
+        depth: 20..120,
+        frequency: 0.006,
+    },
+    Deposit {
+        material: SolidMaterial::GoldOre,
+        shape: Shape::Vein { scale: 24.0 },
+        host: &[Stratum::Igneous, Stratum::Metamorphic],
+        depth: 60..i32::MAX,
+        frequency: 0.008,
+    },
+    Deposit {
+        material: SolidMaterial::CopperOre,
+        shape: Shape::Vein { scale: 16.0 },
+        host: &[Stratum::Igneous, Stratum::Metamorphic],
+        depth: 20..160,
+        frequency: 0.02,
+    },
+    Deposit {
+        material: SolidMaterial::IronOre,
+        shape: Shape::Vein { scale: 16.0 },
+        host: &[Stratum::Sedimentary, Stratum::Igneous],
+        depth: 10..120,
+        frequency: 0.03,
+    },
+    Deposit {
+        material: SolidMaterial::Coal,
+        shape: Shape::Vein { scale: 12.0 },
+        host: &[Stratum::Sedimentary],
+        depth: 5..60,
+        frequency: 0.03,
+    },
+];
+
+impl Deposit {
+    /// Returns true if the deposit can be found at the given position. `richness` scales the
+    /// frequency of the deposit.
+    pub(crate) fn contains(
+        &self,
+        noise: &OpenSimplex,
+        position: IVec3,
+        depth: i32,
+        stratum: Stratum,
+        richness: f64,
+    ) -> bool {
+        if !self.depth.contains(&depth) || !self.host.contains(&stratum) {
+            return false;
+        }
+        let frequency = (self.frequency * richness).min(1.0);
+        match self.shape {
+            Shape::Vein { scale } => {
+                // a vein is where two independent noise fields both cross zero
+                let width = frequency.sqrt() * 0.25;
+                let point = position.as_dvec3() / scale;
+                noise.get(point.to_array()).abs() < width
+                    && noise.get((point + 1000.0).to_array()).abs() < width
+            }
+            Shape::Cluster { scale } => {
+                // open simplex noise peaks at about 0.53, fitted to the tail of its distribution
+                let threshold = 0.53 * (1.0 - frequency.powf(0.27));
+                let point = position.as_dvec3() / scale;
+                noise.get(point.to_array()) > threshold
+            }
+        }
+    }
+}
+
+#[test]
+fn test_deposit_bounds() {
+    let noise = OpenSimplex::new(0);
+    let coal = &DEPOSITS[6];
+    assert_eq!(coal.material, SolidMaterial::Coal);
+    for x in 0..32 {
+        let position = IVec3::new(x, 0, 0);
+        assert!(!coal.contains(&noise, position, 10, Stratum::Igneous, 1.0));
+        assert!(!coal.contains(&noise, position, 100, Stratum::Sedimentary, 1.0));
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::Mutex,
+};
+
+use bevy::prelude::*;
+
+use crate::terrain::Terrain;
+
+/// Rivers and lakes are traced on a coarse grid of cells, this many blocks wide.
+const CELL_SIZE: i32 = 8;
+/// Rivers and lakes are traced for a whole region of cells at once.
+const REGION_SIZE: i32 = 16;
+/// How many cells a river may flow before it seeps away.
+const MAX_RIVER_LENGTH: usize = 60;
+/// How many cells a lake may cover. Larger basins stay dry.
+const MAX_LAKE_CELLS: usize = 48;
+/// How many regions around an area have to be traced, so that every river or lake reaching into
+/// the area is known.
+const REGION_RADIUS: i32 = 4;
+/// How many traced regions are kept. Beyond that, the least recently used ones are dropped and
+/// traced again when they are needed.
+const CACHED_REGIONS: usize = 256;
+
+/// Water on top of a single column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SurfaceWater {
+    /// A river carves its bed into the ground. Blocks below `level` are water.
+    River { level: i32 },
+    /// A lake fills a basin. Blocks below `level` are water.
+    Lake { level: i32 },
+}
+
+impl SurfaceWater {
+    fn level(&self) -> i32 {
+        match self {
+            SurfaceWater::River { level } | SurfaceWater::Lake { level } => *level,
+        }
+    }
+
+    /// Combines two bodies of water on the same column. The result doesn't depend on the order,
+    /// so it doesn't matter which region has been traced first.
+    fn merge(self, other: SurfaceWater) -> SurfaceWater {
+        match self.level().cmp(&other.level()) {
+            std::cmp::Ordering::Less => self,
+            std::cmp::Ordering::Greater => other,
+            std::cmp::Ordering::Equal if matches!(self, SurfaceWater::River { .. }) => self,
+            std::cmp::Ordering::Equal => other,
+        }
+    }
+}
+
+/// Rivers and lakes depend on the terrain far away from them, so they are traced per region
+/// and cached.
+#[derive(Default)]
+pub(crate) struct Hydrology(Mutex<HydrologyCache>);
+
+#[derive(Default)]
+struct HydrologyCache {
+    regions: HashMap<IVec2, TracedRegion>,
+    /// Counts the lookups, to tell which regions were used least recently.
+    clock: u64,
+}
+
+/// The rivers and lakes of a single region.
+#[derive(Default)]
+struct TracedRegion {
+    /// The water the region adds, by the region the columns lie in. Rivers and lakes may reach
+    /// into the regions around it.
+    water: HashMap<IVec2, HashMap<IVec2, SurfaceWater>>,
+    last_used: u64,
+}
+
+impl Hydrology {
+    /// Returns all rivers and lakes within the given columns, tracing them if necessary.
+    pub(crate) fn surface_water(
+        &self,
+        terrain: &Terrain,
+        origin: IVec2,
+        size: IVec2,
+    ) -> HashMap<IVec2, SurfaceWater> {
+        let mut cache = self.0.lock().unwrap();
+        cache.clock += 1;
+        let clock = cache.clock;
+        let region_size = IVec2::splat(CELL_SIZE * REGION_SIZE);
+        let area_min = origin.div_euclid(region_size);
+        let area_max = (origin + size - 1).div_euclid(region_size);
+        let (min, max) = (area_min - REGION_RADIUS, area_max + REGION_RADIUS);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let region = IVec2::new(x, y);
+                cache
+                    .regions
+                    .entry(region)
+                    .or_insert_with(|| TracedRegion::trace(terrain, region))
+                    .last_used = clock;
+            }
+        }
+        cache.evict(clock);
+
+        let mut water: HashMap<IVec2, SurfaceWater> = HashMap::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let traced = &cache.regions[&IVec2::new(x, y)];
+                for area_x in area_min.x..=area_max.x {
+                    for area_y in area_min.y..=area_max.y {
+                        let Some(columns) = traced.water.get(&IVec2::new(area_x, area_y)) else {
+                            continue;
+                        };
+                        for (column, surface_water) in columns {
+                            if column.cmpge(origin).all() && column.cmplt(origin + size).all() {
+                                add(&mut water, *column, *surface_water);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        water
+    }
+}
+
+impl HydrologyCache {
+    /// Drops the least recently used regions until no more than [`CACHED_REGIONS`] are left.
+    /// Regions used by the current lookup are kept, even if that means going over.
+    fn evict(&mut self, clock: u64) {
+        let excess = self.regions.len().saturating_sub(CACHED_REGIONS);
+        if excess == 0 {
+            return;
+        }
+        let mut unused: Vec<(u64, IVec2)> = self
+            .regions
+            .iter()
+            .filter(|(_, traced)| traced.last_used < clock)
+            .map(|(region, traced)| (traced.last_used, *region))
+            .collect();
+        unused.sort_by_key(|(last_used, region)| (*last_used, region.x, region.y));
+        for (_, region) in unused.into_iter().take(excess) {
+            self.regions.remove(&region);
+        }
+    }
+}
+
+impl TracedRegion {
+    fn trace(terrain: &Terrain, region: IVec2) -> Self {
+        let mut traced = TracedRegion::default();
+        for x in 0..REGION_SIZE {
+            for y in 0..REGION_SIZE {
+                let cell = region * REGION_SIZE + IVec2::new(x, y);
+                if terrain.is_river_source(cell, cell_height(terrain, cell)) {
+                    traced.trace_river(terrain, cell);
+                }
+                if is_basin(terrain, cell)
+                    && let Some((level, cells)) = fill_lake(terrain, cell)
+                {
+                    traced.add_lake(terrain, level, &cells);
+                }
+            }
+        }
+        traced
+    }
+
+    fn trace_river(&mut self, terrain: &Terrain, source: IVec2) {
+        let course = river_course(terrain, source);
+        for (length, pair) in course.windows(2).enumerate() {
+            let [(current, _), (next, level)] = pair else {
+                unreachable!()
+            };
+            let radius = 1 + length as i32 / 24;
+            let from = cell_center(*current);
+            let to = cell_center(*next);
+            for step in 0..=CELL_SIZE {
+                let point = from + (to - from) * step / CELL_SIZE;
+                for x in -radius..=radius {
+                    for y in -radius..=radius {
+                        self.add(
+                            point + IVec2::new(x, y),
+                            SurfaceWater::River { level: *level },
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_lake(&mut self, terrain: &Terrain, level: i32, cells: &[IVec2]) {
+        for cell in cells {
+            for x in 0..CELL_SIZE {
+                for y in 0..CELL_SIZE {
+                    let column = cell * CELL_SIZE + IVec2::new(x, y);
+                    if terrain.surface_height(column.x, column.y) < level {
+                        self.add(column, SurfaceWater::Lake { level });
+                    }
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, column: IVec2, surface_water: SurfaceWater) {
+        let region = column.div_euclid(IVec2::splat(CELL_SIZE * REGION_SIZE));
+        add(self.water.entry(region).or_default(), column, surface_water);
+    }
+}
+
+fn add(water: &mut HashMap<IVec2, SurfaceWater>, column: IVec2, surface_water: SurfaceWater) {
+    water
+        .entry(column)
+        .and_modify(|water| *water = water.merge(surface_water))
+        .or_insert(surface_water);
+}
+
+/// Follows the steepest descent from the source, until the river reaches the ocean or a basin.
+///
+/// Returns the cells the river flows through, with the water level in each of them.
+fn river_course(terrain: &Terrain, source: IVec2) -> Vec<(IVec2, i32)> {
+    let mut course = vec![(source, cell_height(terrain, source))];
+    while course.len() <= MAX_RIVER_LENGTH {
+        let (current, level) = *course.last().unwrap();
+        if terrain.is_ocean(cell_center(current)) {
+            break;
+        }
+        let Some((next, next_height)) = lowest_neighbor(terrain, current) else {
+            // a basin, the lake takes over from here
+            break;
+        };
+        // rivers never flow uphill, even if there's a ridge between two cells
+        course.push((next, level.min(next_height)));
+    }
+    course
+}
+
+fn neighbors(cell: IVec2) -> impl Iterator<Item = IVec2> {
+    [
+        IVec2::new(-1, 1),
+        IVec2::new(0, 1),
+        IVec2::new(1, 1),
+        IVec2::new(-1, 0),
+        IVec2::new(1, 0),
+        IVec2::new(-1, -1),
+        IVec2::new(0, -1),
+        IVec2::new(1, -1),
+    ]
+    .into_iter()
+    .map(move |offset| cell + offset)
+}
+
+fn cell_center(cell: IVec2) -> IVec2 {
+    cell * CELL_SIZE + CELL_SIZE / 2
+}
+
+fn cell_height(terrain: &Terrain, cell: IVec2) -> i32 {
+    let center = cell_center(cell);
+    terrain.surface_height(center.x, center.y)
+}
+
+/// Returns the lowest neighbor of a cell, if it is lower than the cell itself.
+fn lowest_neighbor(terrain: &Terrain, cell: IVec2) -> Option<(IVec2, i32)> {
+    let height = cell_height(terrain, cell);
+    neighbors(cell)
+        .map(|neighbor| (neighbor, cell_height(terrain, neighbor)))
+        .filter(|(_, neighbor_height)| *neighbor_height < height)
+        .min_by_key(|(neighbor, neighbor_height)| (*neighbor_height, neighbor.x, neighbor.y))
+}
+
+/// A basin is a cell above the ocean, whose neighbors are all at least as high as itself.
+fn is_basin(terrain: &Terrain, cell: IVec2) -> bool {
+    lowest_neighbor(terrain, cell).is_none() && !terrain.is_ocean(cell_center(cell))
+}
+
+/// Fills a basin with water until it would spill over, by always flooding the lowest cell on its
+/// shore next.
+///
+/// Returns the water level and the flooded cells, or none if the basin is too large.
+fn fill_lake(terrain: &Terrain, basin: IVec2) -> Option<(i32, Vec<IVec2>)> {
+    let mut lake = vec![basin];
+    let mut seen = HashSet::from([basin]);
+    let mut shore = BinaryHeap::new();
+    let mut level = cell_height(terrain, basin);
+    let mut push_neighbors = |cell: IVec2, shore: &mut BinaryHeap<_>| {
+        for neighbor in neighbors(cell) {
+            if seen.insert(neighbor) {
+                let height = cell_height(terrain, neighbor);
+                shore.push(Reverse((height, neighbor.x, neighbor.y)));
+            }
+        }
+    };
+    push_neighbors(basin, &mut shore);
+    while let Some(Reverse((height, x, y))) = shore.pop() {
+        let cell = IVec2::new(x, y);
+        if height >= level {
+            // water would flow off over this cell, if there's lower ground behind it
+            let spills = terrain.is_ocean(cell_center(cell))
+                || neighbors(cell).any(|neighbor| {
+                    !lake.contains(&neighbor) && cell_height(terrain, neighbor) < height
+                });
+            if spills {
+                return Some((height, lake));
+            }
+            level = height;
+        }
+        lake.push(cell);
+        if lake.len() > MAX_LAKE_CELLS {
+            return None;
+        }
+        push_neighbors(cell, &mut shore);
+    }
+    None
+}
+
+#[test]
+fn test_merge_is_commutative() {
+    let river = SurfaceWater::River { level: 4 };
+    let lake = SurfaceWater::Lake { level: 4 };
+    let low_lake = SurfaceWater::Lake { level: 2 };
+    assert_eq!(river.merge(lake), lake.merge(river));
+    assert_eq!(river.merge(lake), river);
+    assert_eq!(river.merge(low_lake), low_lake.merge(river));
+    assert_eq!(river.merge(low_lake), low_lake);
+}
+
+#[test]
+fn test_rivers_flow_downhill() {
+    use crate::map_generation::WorldGenerationSettings;
+
+    let terrain = Terrain::new(&WorldGenerationSettings {
+        river_density: 1.0,
+        ..default()
+    });
+    let mut rivers = 0;
+    for x in -32..32 {
+        for y in -32..32 {
+            let source = IVec2::new(x, y);
+            if !terrain.is_river_source(source, cell_height(&terrain, source)) {
+                continue;
+            }
+            rivers += 1;
+            let course = river_course(&terrain, source);
+            for pair in course.windows(2) {
+                let [(current, level), (next, next_level)] = pair else {
+                    unreachable!()
+                };
+                assert!(neighbors(*current).any(|neighbor| neighbor == *next));
+                assert!(cell_height(&terrain, *next) < cell_height(&terrain, *current));
+                assert!(next_level <= level, "{source}: {course:?}");
+            }
+            // rivers end in the ocean or a basin, unless they seep away
+            let (end, _) = *course.last().unwrap();
+            assert!(
+                course.len() > MAX_RIVER_LENGTH
+                    || terrain.is_ocean(cell_center(end))
+                    || lowest_neighbor(&terrain, end).is_none(),
+                "{source}: {course:?}"
+            );
+        }
+    }
+    assert!(rivers > 0);
+}
+
+#[test]
+fn test_lakes_fill_basins() {
+    use crate::map_generation::WorldGenerationSettings;
+
+    let terrain = Terrain::new(&WorldGenerationSettings::default());
+    let mut lakes = 0;
+    for x in -64..64 {
+        for y in -64..64 {
+            let basin = IVec2::new(x, y);
+            if !is_basin(&terrain, basin) {
+                continue;
+            }
+            let Some((level, cells)) = fill_lake(&terrain, basin) else {
+                continue;
+            };
+            lakes += 1;
+            assert!(cells.contains(&basin));
+            assert!(cells.len() <= MAX_LAKE_CELLS);
+            // the water stands up to the lowest point of the rim
+            for cell in &cells {
+                assert!(cell_height(&terrain, *cell) <= level, "{basin}");
+                for neighbor in neighbors(*cell).filter(|neighbor| !cells.contains(neighbor)) {
+                    assert!(cell_height(&terrain, neighbor) >= level, "{basin}");
+                }
+            }
+        }
+    }
+    assert!(lakes > 0);
+}
+
+#[test]
+fn test_cache_is_bounded() {
+    use crate::map_generation::WorldGenerationSettings;
+
+    let terrain = Terrain::new(&WorldGenerationSettings::default());
+    let hydrology = Hydrology::default();
+    let size = IVec2::splat(256);
+    let region_size = CELL_SIZE * REGION_SIZE;
+    let origin = IVec2::splat(-128);
+    let water = hydrology.surface_water(&terrain, origin, size);
+    assert!(!water.is_empty());
+    // walking far enough to trace every region again
+    for step in 1..8 {
+        let far = IVec2::new(step * region_size * (REGION_RADIUS * 2 + 1), 0);
+        hydrology.surface_water(&terrain, far, size);
+        assert!(hydrology.0.lock().unwrap().regions.len() <= CACHED_REGIONS);
+    }
+    // dropped regions are traced again the same way
+    assert_eq!(hydrology.surface_water(&terrain, origin, size), water);
+}
@@ -1,8 +1,20 @@
+pub mod biome;
 pub mod block_type;
 mod chunk;
 pub mod chunk_visualisation;
+mod deposits;
+mod hydrology;
 pub mod map_generation;
 pub mod messages;
+pub mod movement;
+pub mod overview;
+pub mod passage;
+pub mod queries;
+pub mod residency;
+mod terrain;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod vegetation;
 pub mod world_map;
 
 pub use map_generation::plugin;
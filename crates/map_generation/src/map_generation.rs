@@ -1,27 +1,123 @@
 use bevy::prelude::*;
 use bevy_ecs_tilemap::TilemapPlugin;
-use common::{constants::TILE_SIZE, states::AppState};
+use common::{
+    coordinates::{IWorldCoordinates, TILEMAP_OFFSET},
+    resources::MapBounds,
+    states::AppState,
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Reflect, Resource)]
+/// Everything the world is generated from. Missing fields are filled with their defaults when
+/// deserializing, so older saves and presets can still be read.
+#[derive(Clone, Deserialize, Reflect, Resource, Serialize)]
 #[reflect(Resource)]
+#[serde(default)]
 pub struct WorldGenerationSettings {
     pub seed: u32,
+    /// How many blocks the world spans along x and y, centered on the origin.
+    pub map_size: UVec2,
+    /// The highest peaks and deepest valleys are this many blocks away from z 0.
+    pub elevation: f64,
+    /// How many blocks it takes for the terrain to rise or fall noticeably.
+    pub terrain_scale: f64,
+    /// How strong smaller hills are, compared to the large ones. 0.0 is smooth terrain.
+    pub roughness: f64,
+    /// How many blocks below z 0 the map ends.
+    pub depth: u32,
+    /// Every block below this z coordinate, that is part of a larger low area, is flooded by the ocean.
+    pub ocean_level: i32,
+    /// The chance of a river springing from any 8x8 area of high ground.
+    pub river_density: f64,
+    /// Roughly how much of the underground is hollowed out by caves, between 0.0 and 1.0.
+    pub cave_density: f64,
+    /// Layers of large caverns, from top to bottom.
+    pub caverns: Vec<CavernLayer>,
+    /// Scales how often ores and gems are found.
+    pub mineral_richness: f64,
+    pub biomes: BiomeSettings,
+}
+
+impl Default for WorldGenerationSettings {
+    fn default() -> Self {
+        WorldGenerationSettings {
+            seed: 0,
+            map_size: UVec2::splat(1024),
+            elevation: 100.0,
+            terrain_scale: 16.0,
+            roughness: 0.0,
+            depth: 200,
+            ocean_level: 0,
+            river_density: 0.02,
+            cave_density: 0.02,
+            caverns: vec![
+                CavernLayer { z: -60, height: 8 },
+                CavernLayer {
+                    z: -120,
+                    height: 14,
+                },
+            ],
+            mineral_richness: 1.0,
+            biomes: BiomeSettings::default(),
+        }
+    }
+}
+
+/// A layer of large caverns, spanning the whole world.
+#[derive(Clone, Debug, Deserialize, Reflect, Serialize)]
+pub struct CavernLayer {
+    /// The z coordinate of the cavern floors. The floors are uneven, so this is only a rough value.
+    pub z: i32,
+    /// The height of the largest caverns in this layer.
+    pub height: u32,
+}
+
+/// The area of the world the dwarves start in, picked on the world overview.
+#[derive(Clone, Copy, Debug, Reflect, Resource)]
+#[reflect(Resource)]
+pub struct EmbarkSite(pub IRect);
+
+impl Default for EmbarkSite {
+    fn default() -> Self {
+        EmbarkSite(IRect::from_center_half_size(IVec2::ZERO, IVec2::splat(24)))
+    }
+}
+
+impl EmbarkSite {
+    /// Returns the bounds of a map spawned at this site. It reaches from the configured depth to
+    /// the top of the highest possible tree.
+    pub fn bounds(&self, settings: &WorldGenerationSettings) -> MapBounds {
+        let top = settings.elevation.ceil() as i32 + MAX_TREE_HEIGHT;
+        MapBounds {
+            min: self.0.min.extend(-(settings.depth as i32)),
+            max: (self.0.max - 1).extend(top),
+        }
+    }
 }
 
+/// Inserted when a new world is spawned. Holds the spot within the [`EmbarkSite`] the dwarves
+/// arrive at.
+#[derive(Clone, Copy, Debug, Resource)]
+pub struct Embark(pub IWorldCoordinates);
+
 use crate::{
+    biome::BiomeSettings,
     chunk_visualisation,
     messages::{BlockUpdate, UpdateMap},
-    world_map::WorldMap,
+    residency,
+    vegetation::MAX_TREE_HEIGHT,
+    world_map::{WorldMap, WorldMapSave},
 };
 
 pub fn plugin(app: &mut App) {
     app.register_type::<WorldMap>()
         .register_type::<WorldGenerationSettings>()
+        .register_type::<EmbarkSite>()
+        .init_resource::<EmbarkSite>()
         .add_message::<UpdateMap>()
         .add_message::<BlockUpdate>()
         .insert_resource(ClearColor(Color::srgb_u8(50, 45, 52)))
         .add_plugins(TilemapPlugin)
-        .add_plugins(chunk_visualisation::plugin)
+        .add_plugins((chunk_visualisation::plugin, residency::plugin))
         .add_systems(OnEnter(AppState::MainGame), spawn_world)
         .add_systems(
             Update,
@@ -29,17 +125,34 @@ pub fn plugin(app: &mut App) {
         );
 }
 
-fn spawn_world(world_generation_settings: Res<WorldGenerationSettings>, mut commands: Commands) {
+fn spawn_world(
+    world_generation_settings: Res<WorldGenerationSettings>,
+    embark_site: Res<EmbarkSite>,
+    world_map_save: Option<Res<WorldMapSave>>,
+    mut commands: Commands,
+) {
     let entity = commands
         .spawn((
             Name::new("World Map"),
             // Transform::default(),
-            // due to an issue with bevy_ecs_tilemap, we have to move the whole world by half a tile
-            Transform::from_translation((-TILE_SIZE / 2.0).extend(0.0)),
+            Transform::from_translation(TILEMAP_OFFSET.extend(0.0)),
             Visibility::Inherited,
         ))
         .id();
-    commands.insert_resource(WorldMap::new(entity, world_generation_settings.seed));
+    let world_map = match world_map_save {
+        Some(save) => {
+            commands.remove_resource::<WorldMapSave>();
+            WorldMap::from_save(entity, &world_generation_settings, &save)
+        }
+        None => {
+            let bounds = embark_site.bounds(&world_generation_settings);
+            let world_map = WorldMap::new(entity, &world_generation_settings, bounds);
+            commands.insert_resource(Embark(world_map.embark_position(embark_site.0)));
+            world_map
+        }
+    };
+    commands.insert_resource(world_map.bounds());
+    commands.insert_resource(world_map);
 }
 
 fn handle_messages(
@@ -63,6 +176,11 @@ fn handle_messages(
                     message_writer.write(BlockUpdate::ScheduleForRemoval(world_coordinates));
                 }
             }
+            UpdateMap::PlaceBlock(world_coordinates, block) => {
+                if world_map.place_block(world_coordinates, block) {
+                    message_writer.write(BlockUpdate::Added(world_coordinates));
+                }
+            }
         }
     }
 }
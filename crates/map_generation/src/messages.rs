@@ -1,18 +1,24 @@
 use bevy::prelude::*;
-use common::types::IWorldCoordinates;
+use common::coordinates::IWorldCoordinates;
+
+use crate::block_type::BlockType;
 
 /// Messages to tell the map to update specific contents.
 #[derive(Message)]
 pub enum UpdateMap {
     /// Tells the map to damage a block with the given coordinates and the
-    /// given damage
+    /// given damage. A block of dirt is destroyed by a damage of 1.0, see
+    /// [`crate::block_type::BlockType::durability`]
     DamageBlock(IWorldCoordinates, f32),
     ScheduleForRemoval(IWorldCoordinates),
+    /// Tells the map to place a block at the given coordinates, if there
+    /// is nothing there yet
+    PlaceBlock(IWorldCoordinates, BlockType),
 }
 
 #[derive(Message)]
 pub enum BlockUpdate {
-    Added,
+    Added(IWorldCoordinates),
     Removed(IWorldCoordinates),
     ScheduleForRemoval(IWorldCoordinates),
 }
@@ -0,0 +1,244 @@
+use bevy::prelude::*;
+use common::coordinates::IWorldCoordinates;
+
+use crate::{block_type::BlockType, passage::Passage, world_map::WorldMap};
+
+/// How much farther changing a layer is than moving one block along it.
+pub const CLIMB_COST: f32 = 1.5;
+
+/// The lowest cost of leaving any block, see [`WorldMap::footing`].
+pub const MIN_TRAVERSAL_COST: f32 = 1.0;
+
+/// Returns how far apart two blocks are when moving between them along the grid: diagonals on a
+/// layer count as `√2`, every layer changed as [`CLIMB_COST`].
+///
+/// For neighbors this is the length of the move. Further apart, it never overestimates the moves
+/// needed, so multiplied with [`MIN_TRAVERSAL_COST`] it is an admissible heuristic.
+pub fn distance(from: IVec3, to: IVec3) -> f32 {
+    let offset = (to - from).abs();
+    let (long, short) = (offset.x.max(offset.y), offset.x.min(offset.y));
+    (long - short) as f32 + short as f32 * std::f32::consts::SQRT_2 + offset.z as f32 * CLIMB_COST
+}
+
+/// The rules for how entities move through the map.
+impl WorldMap {
+    /// Returns the cost of leaving the block if an entity can stand in it.
+    ///
+    /// Entities stand in passable blocks above solid ground, or in passages that carry themselves.
+    pub fn footing(&self, coordinates: IVec3) -> Option<f32> {
+        let block = self.get_block(IWorldCoordinates(coordinates))?;
+        if !block.is_passable() {
+            return None;
+        }
+        if let Some(passage) = block.passage()
+            && passage.is_self_supporting()
+        {
+            return Some(passage.traversal_cost());
+        }
+        let material = self
+            .get_block(IWorldCoordinates(coordinates - IVec3::Z))?
+            .material()?;
+        Some(material.traversal_cost())
+    }
+
+    /// Returns true if an entity can move between the neighbors `from` and `to`. Whether it can
+    /// stand in `to` is up to [`Self::footing`].
+    ///
+    /// Layers can only be changed by climbing a passage, or by stepping up or down onto a block
+    /// sharing a side with the current one, as long as nothing blocks the way above the lower block.
+    /// Ramps allow stepping in any direction, diagonals included.
+    pub fn can_move(&self, from: IVec3, to: IVec3) -> bool {
+        let offset = to - from;
+        if offset.z == 0 {
+            return true;
+        }
+        let from_block = self.block_or_empty(from);
+        let to_block = self.block_or_empty(to);
+        if offset.truncate() == IVec2::ZERO {
+            // straight up or down, only along stairs and ladders
+            let (lower, upper) = if offset.z > 0 {
+                (from_block, to_block)
+            } else {
+                (to_block, from_block)
+            };
+            return lower.passage().is_some_and(|passage| passage.leads_up())
+                && upper.passage().is_some_and(|passage| passage.leads_down());
+        }
+        let (lower, lower_block) = if offset.z > 0 {
+            (from, from_block)
+        } else {
+            (to, to_block)
+        };
+        let is_ramp = lower_block.passage() == Some(Passage::Ramp);
+        let is_step = offset.truncate().abs().element_sum() == 1;
+        (is_ramp || is_step) && self.block_or_empty(lower + IVec3::Z).is_passable()
+    }
+
+    /// Returns the cost of moving between the neighbors `from` and `to`, or None if entities can't.
+    ///
+    /// The cost is the [`distance`] of the move, weighed by the [`Self::footing`] of `to`.
+    pub fn move_cost(&self, from: IVec3, to: IVec3) -> Option<f32> {
+        if !self.can_move(from, to) {
+            return None;
+        }
+        Some(self.footing(to)? * distance(from, to))
+    }
+
+    fn block_or_empty(&self, coordinates: IVec3) -> BlockType {
+        self.get_block(IWorldCoordinates(coordinates))
+            .unwrap_or(BlockType::None)
+    }
+}
+
+#[test]
+fn test_vertical_movement() {
+    use crate::testing::STONE as stone;
+
+    // a floor at z 500, with a ledge one block higher on its eastern side
+    let mut world_map = crate::testing::floor(0..8, 0..8);
+    for y in 0..8 {
+        world_map.set_block(IWorldCoordinates(IVec3::new(6, y, 501)), stone);
+        world_map.set_block(IWorldCoordinates(IVec3::new(7, y, 501)), stone);
+    }
+    // everything an entity standing in a block can move to
+    let neighbors = |world_map: &WorldMap, coordinates: IVec3| -> Vec<IVec3> {
+        common::traits::Neighbors::all_neighbors(&coordinates)
+            .into_iter()
+            .map(|(neighbor, _)| neighbor)
+            .filter(|neighbor| {
+                world_map.can_move(coordinates, *neighbor) && world_map.footing(*neighbor).is_some()
+            })
+            .collect()
+    };
+
+    // stepping up onto the ledge works through its side, but not diagonally
+    let below_ledge = IVec3::new(5, 3, 501);
+    let reachable = neighbors(&world_map, below_ledge);
+    assert!(reachable.contains(&IVec3::new(6, 3, 502)));
+    assert!(!reachable.contains(&IVec3::new(6, 4, 502)));
+    assert!(neighbors(&world_map, IVec3::new(6, 3, 502)).contains(&below_ledge));
+
+    // a ramp allows the diagonal
+    world_map.set_block(
+        IWorldCoordinates(below_ledge),
+        BlockType::Passage(Passage::Ramp),
+    );
+    assert!(neighbors(&world_map, below_ledge).contains(&IVec3::new(6, 4, 502)));
+
+    // a ceiling blocks the step
+    world_map.set_block(IWorldCoordinates(below_ledge + IVec3::Z), stone);
+    assert!(!neighbors(&world_map, below_ledge).contains(&IVec3::new(6, 3, 502)));
+
+    // nobody climbs straight up without stairs
+    let bottom = IVec3::new(2, 2, 501);
+    let top = bottom + IVec3::Z;
+    assert!(!neighbors(&world_map, bottom).contains(&top));
+    world_map.set_block(
+        IWorldCoordinates(bottom),
+        BlockType::Passage(Passage::UpStair),
+    );
+    world_map.set_block(
+        IWorldCoordinates(top),
+        BlockType::Passage(Passage::DownStair),
+    );
+    assert!(neighbors(&world_map, bottom).contains(&top));
+    assert!(neighbors(&world_map, top).contains(&bottom));
+    // up stairs alone don't lead down
+    world_map.set_block(IWorldCoordinates(top), BlockType::Passage(Passage::UpStair));
+    assert!(!neighbors(&world_map, top).contains(&bottom));
+}
+
+#[test]
+fn test_distance() {
+    use std::f32::consts::SQRT_2;
+
+    assert_eq!(distance(IVec3::ZERO, IVec3::X), 1.0);
+    assert_eq!(distance(IVec3::ZERO, IVec3::new(1, -1, 0)), SQRT_2);
+    assert_eq!(
+        distance(IVec3::ZERO, IVec3::new(0, 1, -1)),
+        1.0 + CLIMB_COST
+    );
+    assert_eq!(
+        distance(IVec3::ZERO, IVec3::new(-5, 3, 0)),
+        2.0 + 3.0 * SQRT_2
+    );
+    // a detour over a neighbor is never shorter
+    for (neighbor, _) in common::traits::Neighbors::all_neighbors(&IVec3::ZERO) {
+        let far = neighbor * 3 + IVec3::new(2, -1, 1);
+        assert!(
+            distance(IVec3::ZERO, far)
+                <= distance(IVec3::ZERO, neighbor) + distance(neighbor, far) + 1e-4
+        );
+    }
+}
+
+#[test]
+fn test_caverns_are_walkable() {
+    use crate::map_generation::{CavernLayer, WorldGenerationSettings};
+    use common::coordinates::ChunkCoordinates;
+
+    let settings = WorldGenerationSettings {
+        cave_density: 0.0,
+        caverns: vec![CavernLayer { z: -60, height: 8 }],
+        ..default()
+    };
+    let mut world_map = WorldMap::new(
+        Entity::PLACEHOLDER,
+        &settings,
+        common::resources::MapBounds::default(),
+    );
+    for x in 0..2 {
+        for y in 0..2 {
+            world_map.ensure_surrounding_exist(ChunkCoordinates(IVec3::new(x, y, -4)));
+        }
+    }
+    // hollow blocks right above the rock of a cavern floor
+    let floors: Vec<IVec3> = (0..32)
+        .flat_map(|x| (0..32).flat_map(move |y| (-64..-48).map(move |z| IVec3::new(x, y, z))))
+        .filter(|block| {
+            world_map.get_block(IWorldCoordinates(*block)) == Some(BlockType::None)
+                && world_map
+                    .get_block(IWorldCoordinates(*block - IVec3::Z))
+                    .is_some_and(|below| below.is_solid())
+        })
+        .collect();
+    assert!(!floors.is_empty());
+    for floor in &floors {
+        assert!(world_map.footing(*floor).is_some(), "{floor}");
+    }
+    // and they can be walked along
+    assert!(floors.iter().any(|floor| {
+        floors.contains(&(*floor + IVec3::X)) && world_map.can_move(*floor, *floor + IVec3::X)
+    }));
+}
+
+#[test]
+fn test_min_traversal_cost() {
+    use bevy::reflect::{DynamicEnum, DynamicVariant, Enum, FromReflect, TypeInfo, Typed};
+
+    use crate::block_type::SolidMaterial;
+
+    // every variant, so new ones are checked without being listed here
+    fn variants<T: Enum + FromReflect + Typed>() -> Vec<T> {
+        let TypeInfo::Enum(info) = T::type_info() else {
+            unreachable!()
+        };
+        info.variant_names()
+            .iter()
+            .map(|name| T::from_reflect(&DynamicEnum::new(*name, DynamicVariant::Unit)).unwrap())
+            .collect()
+    }
+
+    for material in variants::<SolidMaterial>() {
+        assert!(
+            material.traversal_cost() >= MIN_TRAVERSAL_COST,
+            "{material:?}"
+        );
+    }
+    for passage in variants::<Passage>() {
+        assert!(
+            passage.traversal_cost() >= MIN_TRAVERSAL_COST,
+            "{passage:?}"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+
+use crate::{
+    biome::{Biome, LiquidBehaviour},
+    block_type::SolidMaterial,
+    map_generation::WorldGenerationSettings,
+    terrain::{Column, Terrain},
+};
+
+/// A map of a large part of the world, seen from above, to choose where to embark.
+///
+/// Every pixel shows a single column, taken every `step` blocks.
+pub struct Overview {
+    /// The column at the bottom left corner.
+    pub origin: IVec2,
+    /// How many blocks one pixel covers along each axis.
+    pub step: i32,
+    pub size: UVec2,
+    /// The color of every pixel, row by row, starting at the top.
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl Overview {
+    /// Generates the overview of `size` pixels around the `center` column, colored by biome,
+    /// water and elevation.
+    pub fn generate(
+        settings: &WorldGenerationSettings,
+        center: IVec2,
+        size: UVec2,
+        step: i32,
+    ) -> Self {
+        let terrain = Terrain::new(settings);
+        let origin = center - size.as_ivec2() * step / 2;
+        let columns = terrain.sample_columns(origin, size.as_ivec2(), step);
+        let mut pixels = vec![[0; 4]; (size.x * size.y) as usize];
+        for (index, column) in columns.iter().enumerate() {
+            let x = index as u32 / size.y;
+            let y = index as u32 % size.y;
+            let row = size.y - 1 - y;
+            pixels[(row * size.x + x) as usize] =
+                color(column, settings.ocean_level).to_srgba().to_u8_array();
+        }
+        Overview {
+            origin,
+            step,
+            size,
+            pixels,
+        }
+    }
+
+    /// Returns the column shown at the given pixel position.
+    pub fn to_column(&self, pixel: Vec2) -> IVec2 {
+        let x = pixel.x.floor() as i32;
+        let y = self.size.y as i32 - 1 - pixel.y.floor() as i32;
+        self.origin + IVec2::new(x, y) * self.step
+    }
+
+    /// Returns the pixel position of the given column, the inverse of [`Overview::to_column`].
+    pub fn to_pixel(&self, column: IVec2) -> Vec2 {
+        let offset = (column - self.origin).as_vec2() / self.step as f32;
+        Vec2::new(offset.x, self.size.y as f32 - 1.0 - offset.y)
+    }
+}
+
+fn color(column: &Column, ocean_level: i32) -> Color {
+    let Some(water_level) = column.water_level else {
+        // higher ground is drawn brighter
+        let height = (column.surface - ocean_level).clamp(-100, 100) as f32 / 100.0;
+        return biome_color(column.biome).mix(&Color::BLACK, 0.3 - height * 0.3);
+    };
+    if column.biome.liquid_behaviour() == LiquidBehaviour::Frozen {
+        return SolidMaterial::Ice.color();
+    }
+    let depth = (water_level - column.surface).clamp(0, 40) as f32 / 40.0;
+    Color::srgb_u8(70, 130, 200).mix(&Color::srgb_u8(20, 40, 110), depth)
+}
+
+fn biome_color(biome: Biome) -> Color {
+    match biome {
+        Biome::Desert => Color::srgb_u8(220, 200, 130),
+        Biome::Grassland => Color::srgb_u8(130, 180, 80),
+        Biome::Forest => Color::srgb_u8(50, 120, 50),
+        Biome::Swamp => Color::srgb_u8(90, 110, 60),
+        Biome::Tundra => Color::srgb_u8(225, 230, 235),
+        Biome::Mountains => Color::srgb_u8(130, 125, 120),
+    }
+}
+
+#[test]
+fn test_pixel_round_trip() {
+    let overview = Overview {
+        origin: IVec2::new(-64, -32),
+        step: 4,
+        size: UVec2::new(32, 16),
+        pixels: vec![],
+    };
+    for column in [IVec2::new(-64, -32), IVec2::new(0, 0), IVec2::new(60, 28)] {
+        assert_eq!(overview.to_column(overview.to_pixel(column)), column);
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TileTextureIndex;
+use serde::{Deserialize, Serialize};
+
+/// Built blocks that connect layers. Entities can only change layers through them, or by
+/// stepping up onto a neighboring block.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
+pub enum Passage {
+    /// Leads to a [`Passage::DownStair`] or [`Passage::UpDownStair`] on the layer above.
+    UpStair,
+    /// Leads to an [`Passage::UpStair`] or [`Passage::UpDownStair`] on the layer below.
+    DownStair,
+    /// Leads both up and down.
+    UpDownStair,
+    /// Leads up to any neighbor on the layer above, including diagonal ones.
+    Ramp,
+    /// Leads both up and down, like an [`Passage::UpDownStair`], but is slower to climb.
+    Ladder,
+}
+
+impl Passage {
+    /// Returns true if this passage can be climbed to the block above.
+    pub const fn leads_up(&self) -> bool {
+        matches!(
+            self,
+            Passage::UpStair | Passage::UpDownStair | Passage::Ladder
+        )
+    }
+
+    /// Returns true if this passage can be climbed to the block below.
+    pub const fn leads_down(&self) -> bool {
+        matches!(
+            self,
+            Passage::DownStair | Passage::UpDownStair | Passage::Ladder
+        )
+    }
+
+    /// Returns true if entities can stand in this passage without solid ground below.
+    /// Ramps are built on the ground, everything else carries itself.
+    pub const fn is_self_supporting(&self) -> bool {
+        !matches!(self, Passage::Ramp)
+    }
+
+    /// Returns the cost of an entity leaving this passage,
+    /// see [`crate::block_type::SolidMaterial::traversal_cost`].
+    pub const fn traversal_cost(&self) -> f32 {
+        match self {
+            Passage::UpStair | Passage::DownStair | Passage::UpDownStair => 1.2,
+            Passage::Ramp => 1.0,
+            Passage::Ladder => 2.0,
+        }
+    }
+
+    /// Returns how much damage the passage takes before it is destroyed, see
+    /// [`crate::block_type::SolidMaterial::durability`].
+    pub const fn durability(&self) -> f32 {
+        match self {
+            Passage::Ladder => 0.5,
+            Passage::UpStair | Passage::DownStair | Passage::UpDownStair | Passage::Ramp => 2.0,
+        }
+    }
+
+    pub(crate) fn color(&self) -> Color {
+        match self {
+            Passage::UpStair => Color::srgb_u8(200, 200, 120),
+            Passage::DownStair => Color::srgb_u8(120, 120, 200),
+            Passage::UpDownStair => Color::srgb_u8(170, 140, 200),
+            Passage::Ramp => Color::srgb_u8(150, 150, 150),
+            Passage::Ladder => Color::srgb_u8(140, 100, 60),
+        }
+    }
+
+    pub(crate) fn tile_texture_index(&self) -> TileTextureIndex {
+        TileTextureIndex(0)
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+use common::{
+    coordinates::IWorldCoordinates,
+    traits::{Connectivity, Neighbors},
+};
+
+use crate::{block_type::BlockType, world_map::WorldMap};
+
+/// The first block a ray hits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub coordinates: IWorldCoordinates,
+    pub block: BlockType,
+    /// The side of the block the ray entered through. Zero if the ray started inside the block.
+    pub normal: IVec3,
+    /// How far along the ray the block was entered, in blocks.
+    pub distance: f32,
+}
+
+/// Spatial queries over the loaded chunks. Blocks in chunks that aren't loaded are never returned.
+impl WorldMap {
+    /// Returns all blocks within the box between `min` and `max`, both inclusive.
+    pub fn blocks_in(
+        &self,
+        min: IWorldCoordinates,
+        max: IWorldCoordinates,
+    ) -> impl Iterator<Item = (IWorldCoordinates, BlockType)> {
+        (min.0.x..=max.0.x).flat_map(move |x| {
+            (min.0.y..=max.0.y).flat_map(move |y| {
+                (min.0.z..=max.0.z).filter_map(move |z| {
+                    let coordinates = IWorldCoordinates(IVec3::new(x, y, z));
+                    self.get_block(coordinates)
+                        .map(|block| (coordinates, block))
+                })
+            })
+        })
+    }
+
+    /// Returns all blocks within the box between `min` and `max` that match the predicate.
+    ///
+    /// To look for a material, match on [`BlockType::material`].
+    pub fn blocks_matching(
+        &self,
+        min: IWorldCoordinates,
+        max: IWorldCoordinates,
+        predicate: impl Fn(&BlockType) -> bool,
+    ) -> impl Iterator<Item = (IWorldCoordinates, BlockType)> {
+        self.blocks_in(min, max)
+            .filter(move |(_, block)| predicate(block))
+    }
+
+    /// Follows a ray from `origin` until it hits a block matching the predicate, leaves the
+    /// loaded chunks or is longer than `max_distance`.
+    ///
+    /// Blocks are centered on their coordinates, like [`common::coordinates::WorldCoordinates`].
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        predicate: impl Fn(&BlockType) -> bool,
+    ) -> Option<RaycastHit> {
+        let direction = direction.try_normalize()?;
+        // shifted, so block borders lie on whole numbers
+        let start = origin + 0.5;
+        let mut current = start.floor().as_ivec3();
+        let step = direction.signum().as_ivec3();
+        // how far along the ray the next border is crossed, per axis
+        let mut next_border = Vec3::ZERO;
+        // how far along the ray it takes to cross a whole block, per axis
+        let mut delta = Vec3::ZERO;
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                next_border[axis] = f32::INFINITY;
+                delta[axis] = f32::INFINITY;
+            } else {
+                let border = if direction[axis] > 0.0 {
+                    current[axis] as f32 + 1.0
+                } else {
+                    current[axis] as f32
+                };
+                next_border[axis] = (border - start[axis]) / direction[axis];
+                delta[axis] = 1.0 / direction[axis].abs();
+            }
+        }
+        let mut normal = IVec3::ZERO;
+        let mut distance = 0.0;
+        while distance <= max_distance {
+            let coordinates = IWorldCoordinates(current);
+            let block = self.get_block(coordinates)?;
+            if predicate(&block) {
+                return Some(RaycastHit {
+                    coordinates,
+                    block,
+                    normal,
+                    distance,
+                });
+            }
+            let axis = next_border.min_position();
+            distance = next_border[axis];
+            next_border[axis] += delta[axis];
+            current[axis] += step[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+        None
+    }
+
+    /// Collects the blocks matching the predicate that are connected to `start` through their
+    /// faces, at most `limit` of them. Returns nothing if `start` doesn't match.
+    ///
+    /// With [`BlockType::is_passable`] as predicate, this returns the space reachable from `start`.
+    pub fn flood_fill(
+        &self,
+        start: IWorldCoordinates,
+        limit: usize,
+        predicate: impl Fn(&BlockType) -> bool,
+    ) -> HashSet<IWorldCoordinates> {
+        let mut filled = HashSet::new();
+        if !self.get_block(start).is_some_and(|block| predicate(&block)) {
+            return filled;
+        }
+        let mut queue = VecDeque::from([start]);
+        filled.insert(start);
+        while let Some(coordinates) = queue.pop_front() {
+            for (neighbor, _) in coordinates.neighbors(Connectivity::Faces) {
+                if filled.len() >= limit {
+                    break;
+                }
+                if !filled.contains(&neighbor)
+                    && self
+                        .get_block(neighbor)
+                        .is_some_and(|block| predicate(&block))
+                {
+                    filled.insert(neighbor);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        filled
+    }
+
+    /// Returns the block matching the predicate that is closest to `origin`, no more than
+    /// `max_distance` blocks away along any axis. Ties are broken by the order of the search.
+    pub fn find_nearest(
+        &self,
+        origin: IWorldCoordinates,
+        max_distance: i32,
+        predicate: impl Fn(&BlockType) -> bool,
+    ) -> Option<(IWorldCoordinates, BlockType)> {
+        let mut nearest: Option<(IWorldCoordinates, BlockType, i32)> = None;
+        for radius in 0..=max_distance {
+            // nothing on this shell or beyond can be closer than what has been found
+            if nearest.is_some_and(|(_, _, squared)| squared <= radius * radius) {
+                break;
+            }
+            let min = IWorldCoordinates(origin.0 - radius);
+            let max = IWorldCoordinates(origin.0 + radius);
+            for (coordinates, block) in self.blocks_in(min, max) {
+                let offset = coordinates.0 - origin.0;
+                // only the shell, the inside has been searched already
+                if offset.abs().max_element() != radius || !predicate(&block) {
+                    continue;
+                }
+                let squared = offset.length_squared();
+                if nearest.is_none_or(|(_, _, best)| squared < best) {
+                    nearest = Some((coordinates, block, squared));
+                }
+            }
+        }
+        nearest.map(|(coordinates, block, _)| (coordinates, block))
+    }
+}
+
+#[test]
+fn test_raycast() {
+    let mut world_map = crate::testing::world_map();
+    let wall = IWorldCoordinates(IVec3::new(5, 2, 500));
+    world_map.place_block(wall, BlockType::Liquid);
+    let hit = world_map
+        .raycast(
+            Vec3::new(0.0, 0.0, 500.0),
+            Vec3::new(5.0, 2.0, 0.0),
+            20.0,
+            |block| *block == BlockType::Liquid,
+        )
+        .unwrap();
+    assert_eq!(hit.coordinates, wall);
+    assert_eq!(hit.normal, IVec3::NEG_X);
+    assert!(
+        world_map
+            .raycast(Vec3::new(0.0, 0.0, 500.0), Vec3::X, 20.0, |block| {
+                *block == BlockType::Liquid
+            })
+            .is_none()
+    );
+}
+
+#[test]
+fn test_flood_fill_and_nearest() {
+    let mut world_map = crate::testing::world_map();
+    let start = IWorldCoordinates(IVec3::new(0, 0, 500));
+    // a pocket of water, two blocks long
+    world_map.set_block(start, BlockType::Liquid);
+    world_map.set_block(IWorldCoordinates(IVec3::new(1, 0, 500)), BlockType::Liquid);
+    world_map.set_block(IWorldCoordinates(IVec3::new(3, 3, 500)), BlockType::Liquid);
+    let water = |block: &BlockType| *block == BlockType::Liquid;
+    assert_eq!(world_map.flood_fill(start, 100, water).len(), 2);
+    assert_eq!(world_map.flood_fill(start, 1, water).len(), 1);
+    let origin = IWorldCoordinates(IVec3::new(4, 1, 500));
+    let (nearest, _) = world_map.find_nearest(origin, 8, water).unwrap();
+    assert_eq!(nearest.0, IVec3::new(3, 3, 500));
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use camera::CameraLayer;
+use common::{
+    coordinates::{ChunkCoordinates, IWorldCoordinates, WorldCoordinates},
+    states::AppState,
+    traits::Neighbors,
+};
+
+use crate::{chunk_visualisation::ChunkVisualisation, world_map::WorldMap};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<ChunkResidencySettings>()
+        .register_type::<ChunkPins>()
+        .init_resource::<ChunkResidencySettings>()
+        .add_systems(
+            Update,
+            evict_chunks
+                .run_if(resource_exists::<WorldMap>)
+                .run_if(in_state(AppState::MainGame)),
+        );
+}
+
+/// Decides how many chunks are kept in memory.
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct ChunkResidencySettings {
+    /// How many chunks may be kept in memory. Chunks that are in use are never evicted, even if
+    /// that means going over budget.
+    pub budget: usize,
+    /// How many chunks around anything living in the world (dwarves, work orders, etc.) are kept.
+    pub radius: i32,
+}
+
+impl Default for ChunkResidencySettings {
+    fn default() -> Self {
+        ChunkResidencySettings {
+            budget: 512,
+            radius: 1,
+        }
+    }
+}
+
+/// Pins chunks, so they stay in memory as long as this component exists.
+///
+/// Use this from systems that depend on chunks that are neither visible nor close to
+/// a [`WorldCoordinates`], like a path leading through the map.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct ChunkPins(Vec<ChunkCoordinates>);
+
+impl ChunkPins {
+    /// Pins every chunk that contains one of the given coordinates.
+    pub fn containing(coordinates: impl IntoIterator<Item = IWorldCoordinates>) -> Self {
+        let mut chunks: Vec<ChunkCoordinates> = vec![];
+        for world_coordinates in coordinates {
+            let (chunk_coordinates, _) = world_coordinates.to_chunk_and_block();
+            if !chunks.contains(&chunk_coordinates) {
+                chunks.push(chunk_coordinates);
+            }
+        }
+        ChunkPins(chunks)
+    }
+
+    /// Pins every chunk that contains one of the given coordinates, together with the chunks
+    /// around it. Those decide where the chunk can be entered and left.
+    pub fn surrounding(coordinates: impl IntoIterator<Item = IWorldCoordinates>) -> Self {
+        let centers: HashSet<ChunkCoordinates> = coordinates
+            .into_iter()
+            .map(|world_coordinates| world_coordinates.chunk())
+            .collect();
+        let mut chunks = centers.clone();
+        for center in centers {
+            chunks.extend(
+                center
+                    .all_neighbors()
+                    .into_iter()
+                    .map(|(neighbor, _)| neighbor),
+            );
+        }
+        ChunkPins(chunks.into_iter().collect())
+    }
+}
+
+/// Evicts chunks that aren't in use, farthest from the camera first, until the budget is met.
+///
+/// Chunks are in use if they are visualised, pinned or close to anything with [`WorldCoordinates`].
+/// Modified chunks can be evicted as well, their delta stays with the [`WorldMap`].
+fn evict_chunks(
+    settings: Res<ChunkResidencySettings>,
+    mut world_map: ResMut<WorldMap>,
+    camera: Single<(&Transform, &CameraLayer), With<Camera2d>>,
+    chunk_visualisations: Query<&ChunkVisualisation>,
+    world_coordinates: Query<&WorldCoordinates>,
+    pins: Query<&ChunkPins>,
+) {
+    let visualised = chunk_visualisations.iter().map(|chunk| (chunk.0, 1));
+    let occupied = world_coordinates.iter().map(|coordinates| {
+        let (chunk_coordinates, _) = coordinates.block().to_chunk_and_block();
+        (chunk_coordinates, settings.radius)
+    });
+    let pinned = pins
+        .iter()
+        .flat_map(|pins| pins.0.iter().map(|pin| (*pin, 0)));
+
+    let mut keep = HashSet::new();
+    for (coordinates, radius) in visualised.chain(occupied).chain(pinned) {
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    keep.insert(coordinates.0 + IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    let (transform, layer) = camera.into_inner();
+    let center =
+        WorldCoordinates::from_translation(transform.translation.truncate(), layer.0 as f32)
+            .block()
+            .chunk();
+    let evicted = world_map.evict(&keep, settings.budget, center.0);
+    if evicted > 0 {
+        debug!(
+            "evicted {} chunks, {} chunks in memory",
+            evicted,
+            world_map.resident_chunks()
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use bevy::{math::DVec3, prelude::*};
+use noise::{NoiseFn, OpenSimplex};
+
+use crate::{
+    biome::{Biome, BiomeSettings, LiquidBehaviour},
+    block_type::{SolidMaterial, Stratum},
+    chunk::CHUNK_SIZE,
+    deposits::DEPOSITS,
+    hydrology::{Hydrology, SurfaceWater},
+    map_generation::{CavernLayer, WorldGenerationSettings},
+    vegetation::{self, Vegetation},
+};
+
+/// Caves never come closer to the surface than this, so they don't break through to the sky
+/// or into the sea.
+const MIN_CAVE_DEPTH: i32 = 5;
+/// How deep rivers are, in blocks of water.
+const RIVER_DEPTH: i32 = 2;
+
+/// The surface of a single column.
+pub(crate) struct Column {
+    pub(crate) coordinates: IVec2,
+    /// The z coordinate of the topmost solid block, before caves are carved out.
+    pub(crate) surface: i32,
+    /// Every block above the surface and below this z coordinate is water.
+    pub(crate) water_level: Option<i32>,
+    pub(crate) biome: Biome,
+}
+
+/// Decides what the world looks like, based on the [`WorldGenerationSettings`].
+///
+/// Every feature uses its own noise, seeded with an offset of the world seed, so they don't line up.
+#[derive(Default)]
+pub(crate) struct Terrain {
+    seed: u32,
+    height: OpenSimplex,
+    strata: OpenSimplex,
+    rock: OpenSimplex,
+    deposits: [OpenSimplex; DEPOSITS.len()],
+    caves: OpenSimplex,
+    caverns: OpenSimplex,
+    aquifers: OpenSimplex,
+    temperature: OpenSimplex,
+    rainfall: OpenSimplex,
+    pools: OpenSimplex,
+    elevation: f64,
+    terrain_scale: f64,
+    roughness: f64,
+    mineral_richness: f64,
+    cave_density: f64,
+    cavern_layers: Vec<CavernLayer>,
+    ocean_level: i32,
+    river_density: f64,
+    biomes: BiomeSettings,
+    hydrology: Hydrology,
+}
+
+impl Terrain {
+    pub(crate) fn new(settings: &WorldGenerationSettings) -> Self {
+        let seed = settings.seed;
+        Terrain {
+            seed,
+            height: OpenSimplex::new(seed),
+            strata: OpenSimplex::new(seed.wrapping_add(1)),
+            rock: OpenSimplex::new(seed.wrapping_add(2)),
+            deposits: std::array::from_fn(|index| {
+                OpenSimplex::new(seed.wrapping_add(3 + index as u32))
+            }),
+            caves: OpenSimplex::new(seed.wrapping_add(3 + DEPOSITS.len() as u32)),
+            caverns: OpenSimplex::new(seed.wrapping_add(4 + DEPOSITS.len() as u32)),
+            aquifers: OpenSimplex::new(seed.wrapping_add(5 + DEPOSITS.len() as u32)),
+            temperature: OpenSimplex::new(seed.wrapping_add(6 + DEPOSITS.len() as u32)),
+            rainfall: OpenSimplex::new(seed.wrapping_add(7 + DEPOSITS.len() as u32)),
+            pools: OpenSimplex::new(seed.wrapping_add(8 + DEPOSITS.len() as u32)),
+            elevation: settings.elevation,
+            terrain_scale: settings.terrain_scale,
+            roughness: settings.roughness,
+            mineral_richness: settings.mineral_richness,
+            cave_density: settings.cave_density,
+            cavern_layers: settings.caverns.clone(),
+            ocean_level: settings.ocean_level,
+            river_density: settings.river_density,
+            biomes: settings.biomes.clone(),
+            hydrology: Hydrology::default(),
+        }
+    }
+
+    /// Returns the columns of an area with their surface water, ordered by x first, then y.
+    pub(crate) fn columns(&self, origin: IVec2, size: IVec2) -> Vec<Column> {
+        self.sample_columns(origin, size, 1)
+    }
+
+    /// Returns every `step`th column of an area, `size` is the number of columns returned along
+    /// each axis. Ordered by x first, then y.
+    pub(crate) fn sample_columns(&self, origin: IVec2, size: IVec2, step: i32) -> Vec<Column> {
+        let surface_water = self.hydrology.surface_water(self, origin, size * step);
+        let mut columns = Vec::with_capacity((size.x * size.y) as usize);
+        for x in 0..size.x {
+            for y in 0..size.y {
+                let column = origin + IVec2::new(x, y) * step;
+                let surface = self.surface_height(column.x, column.y);
+                let biome = self.biome(column, surface);
+                let (surface, water_level) = if self.is_ocean(column) {
+                    (surface, Some(self.ocean_level))
+                } else {
+                    match (surface_water.get(&column), biome.liquid_behaviour()) {
+                        (Some(SurfaceWater::River { level }), _) => {
+                            (surface.min(level - RIVER_DEPTH - 1), Some(*level))
+                        }
+                        (Some(SurfaceWater::Lake { .. }), LiquidBehaviour::Evaporating) => {
+                            (surface, None)
+                        }
+                        (Some(SurfaceWater::Lake { level }), _) => (surface, Some(*level)),
+                        (None, LiquidBehaviour::Pooling) if self.is_pool(column) => {
+                            (surface - 1, Some(surface))
+                        }
+                        (None, _) => (surface, None),
+                    }
+                };
+                columns.push(Column {
+                    coordinates: column,
+                    surface,
+                    water_level,
+                    biome,
+                });
+            }
+        }
+        columns
+    }
+
+    /// Returns the z coordinate of the surface at the given column.
+    /// Rough terrain adds a finer layer of hills on top of the large ones.
+    pub(crate) fn surface_height(&self, x: i32, y: i32) -> i32 {
+        let point = IVec2::new(x, y).as_dvec2() / self.terrain_scale;
+        let hills = self.height.get(point.to_array());
+        let detail = self.height.get((point * 4.0 + 1000.0).to_array());
+        ((hills + detail * self.roughness) / (1.0 + self.roughness))
+            // .remap(-1.0, 1.0, -10984.0, 8848.0)
+            .remap(-1.0, 1.0, -self.elevation, self.elevation)
+            .round() as i32
+    }
+
+    /// Returns the trees, shrubs and plants growing on a column.
+    pub(crate) fn vegetation(&self, column: &Column) -> Vec<(IVec3, Vegetation)> {
+        let rolls = std::array::from_fn(|salt| {
+            random(self.seed.wrapping_add(salt as u32 + 1), column.coordinates)
+        });
+        vegetation::grow(column, self.biomes.vegetation, rolls)
+    }
+
+    /// Returns the biome of a column. It gets colder the higher the surface is.
+    fn biome(&self, column: IVec2, surface: i32) -> Biome {
+        let point = [
+            column.x as f64 / self.biomes.scale,
+            column.y as f64 / self.biomes.scale,
+        ];
+        let height = surface - self.ocean_level;
+        let temperature = self.temperature.get(point) + self.biomes.temperature
+            - self.biomes.lapse_rate * height.max(0) as f64;
+        let rainfall = self.rainfall.get(point) + self.biomes.rainfall;
+        Biome::classify(temperature, rainfall, height, &self.biomes)
+    }
+
+    /// Returns true if a shallow pool forms on the column, in biomes where water pools.
+    fn is_pool(&self, column: IVec2) -> bool {
+        let point = column.as_dvec2() / 6.0;
+        self.pools.get(point.to_array()) > 0.15
+    }
+
+    /// Returns true if the column is below the ocean level and part of a larger low area.
+    /// Small depressions below the ocean level aren't flooded by the ocean, they may hold lakes.
+    pub(crate) fn is_ocean(&self, column: IVec2) -> bool {
+        if self.surface_height(column.x, column.y) >= self.ocean_level {
+            return false;
+        }
+        let mut regional_height = 0;
+        for x in -1..=1 {
+            for y in -1..=1 {
+                let offset = IVec2::new(x, y).as_dvec2() * self.terrain_scale * 3.0;
+                let sample = column + offset.as_ivec2();
+                regional_height += self.surface_height(sample.x, sample.y);
+            }
+        }
+        regional_height < self.ocean_level * 9
+    }
+
+    /// Returns true if a river springs at the given cell.
+    pub(crate) fn is_river_source(&self, cell: IVec2, height: i32) -> bool {
+        height > self.ocean_level + 10 && random(self.seed, cell) < self.river_density
+    }
+
+    /// Aquifers are water-bearing layers at the top of the sedimentary stratum.
+    pub(crate) fn is_aquifer(&self, position: IVec3, depth: i32, biome: Biome) -> bool {
+        let [x, y] = column(position.x, position.y);
+        let [soil, _, _] = self.layers(position, biome);
+        let thickness = self.aquifers.get([x, y]).remap(-1.0, 1.0, -8.0, 8.0);
+        let depth = depth as f64;
+        depth >= soil && depth < soil + thickness
+    }
+
+    /// Returns the material of a solid block, `depth` blocks below the surface.
+    pub(crate) fn material(&self, position: IVec3, depth: i32, biome: Biome) -> SolidMaterial {
+        let stratum = self.stratum(position, depth, biome);
+        for (deposit, noise) in DEPOSITS.iter().zip(&self.deposits) {
+            if deposit.contains(noise, position, depth, stratum, self.mineral_richness) {
+                return deposit.material;
+            }
+        }
+        // larger veins horizontally than vertically, so the rock looks layered
+        let rock = self.rock.get([
+            position.x as f64 / 24.0,
+            position.y as f64 / 24.0,
+            position.z as f64 / 6.0,
+        ]) > 0.0;
+        match (stratum, rock) {
+            (Stratum::Soil, _) => biome.soil_material(),
+            (Stratum::Sedimentary, true) => SolidMaterial::Sandstone,
+            (Stratum::Sedimentary, false) => SolidMaterial::Limestone,
+            (Stratum::Igneous, true) => SolidMaterial::Granite,
+            (Stratum::Igneous, false) => SolidMaterial::Basalt,
+            (Stratum::Metamorphic, true) => SolidMaterial::Marble,
+            (Stratum::Metamorphic, false) => SolidMaterial::Slate,
+        }
+    }
+
+    /// Returns true if a block, `depth` blocks below the surface, is carved out by a cave
+    /// or a cavern.
+    pub(crate) fn is_hollow(&self, position: IVec3, depth: i32) -> bool {
+        depth >= MIN_CAVE_DEPTH && (self.is_cave(position) || self.is_cavern(position))
+    }
+
+    /// Caves are winding tunnels, found where two independent noise fields both cross zero.
+    fn is_cave(&self, position: IVec3) -> bool {
+        // fitted, so that roughly `cave_density` of the underground is hollow
+        let width = self.cave_density.sqrt() / 4.8;
+        let point = position.as_dvec3() / DVec3::new(24.0, 24.0, 12.0);
+        self.caves.get(point.to_array()).abs() < width
+            && self.caves.get((point + 1000.0).to_array()).abs() < width
+    }
+
+    /// Caverns are large hollow spaces inside the cavern layers. They are highest in their
+    /// center and flatten out towards their edges.
+    fn is_cavern(&self, position: IVec3) -> bool {
+        let [x, y] = column(position.x, position.y);
+        self.cavern_layers.iter().enumerate().any(|(index, layer)| {
+            let layer_noise = |offset: f64| {
+                self.caverns
+                    .get([x * 2.0, y * 2.0, index as f64 * 10.0 + offset])
+            };
+            // open simplex rarely exceeds 0.4, so caverns cover about half of a layer
+            let openness = ((layer_noise(0.0) - 0.05) / 0.35).clamp(0.0, 1.0);
+            let floor = layer.z + (layer_noise(5.0) * 4.0).round() as i32;
+            let ceiling = floor + (openness * layer.height as f64).round() as i32;
+            (floor..ceiling).contains(&position.z)
+        })
+    }
+
+    /// Finds the stratum of a block. The thickness of every layer varies from column to column.
+    fn stratum(&self, position: IVec3, depth: i32, biome: Biome) -> Stratum {
+        let [soil, sedimentary, igneous] = self.layers(position, biome);
+        let depth = depth as f64;
+        if depth < soil {
+            Stratum::Soil
+        } else if depth < sedimentary {
+            Stratum::Sedimentary
+        } else if depth < igneous {
+            Stratum::Igneous
+        } else {
+            Stratum::Metamorphic
+        }
+    }
+
+    /// Returns the depths at which the soil, sedimentary and igneous strata end.
+    fn layers(&self, position: IVec3, biome: Biome) -> [f64; 3] {
+        let [x, y] = column(position.x, position.y);
+        let thickness = |layer: f64, min: f64, max: f64| {
+            self.strata.get([x, y, layer]).remap(-1.0, 1.0, min, max)
+        };
+        let soil = thickness(0.0, 2.0, 6.0) * biome.soil_depth();
+        let sedimentary = soil + thickness(10.0, 10.0, 30.0);
+        let igneous = sedimentary + thickness(20.0, 20.0, 50.0);
+        [soil, sedimentary, igneous]
+    }
+}
+
+/// Returns a deterministic random number between 0.0 and 1.0 for a cell.
+fn random(seed: u32, cell: IVec2) -> f64 {
+    // the finalizer of splitmix64
+    let mix = |mut value: u64| {
+        value = value.wrapping_add(0x9e3779b97f4a7c15);
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+        value ^ (value >> 31)
+    };
+    let value = mix(mix((seed as u64) << 32 | cell.x as u32 as u64) ^ cell.y as u32 as u64);
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns the noise coordinates of a column, one unit per chunk.
+fn column(x: i32, y: i32) -> [f64; 2] {
+    [
+        x as f64 / CHUNK_SIZE.x as f64,
+        y as f64 / CHUNK_SIZE.y as f64,
+    ]
+}
+
+#[test]
+fn test_strata_order() {
+    let terrain = Terrain::new(&WorldGenerationSettings::default());
+    let position = IVec3::new(3, 7, 0);
+    assert_eq!(
+        terrain.stratum(position, 1, Biome::Grassland),
+        Stratum::Soil
+    );
+    assert_eq!(
+        terrain.stratum(position, 200, Biome::Grassland),
+        Stratum::Metamorphic
+    );
+    let mut previous = 0;
+    for depth in 1..200 {
+        let stratum = terrain.stratum(position, depth, Biome::Grassland) as usize;
+        assert!(stratum >= previous, "depth: {}", depth);
+        previous = stratum;
+    }
+}
+
+#[test]
+fn test_caves_stay_below_surface() {
+    let terrain = Terrain::new(&WorldGenerationSettings {
+        cave_density: 0.5,
+        caverns: vec![],
+        ..default()
+    });
+    let mut deeper = 0;
+    for x in 0..32 {
+        for y in 0..32 {
+            let surface = terrain.surface_height(x, y);
+            for depth in 0..40 {
+                let hollow = terrain.is_hollow(IVec3::new(x, y, surface - depth), depth);
+                assert!(!hollow || depth >= MIN_CAVE_DEPTH, "{x} {y} {depth}");
+                deeper += hollow as usize;
+            }
+        }
+    }
+    // the caves are there, just deeper down
+    assert!(deeper > 0);
+}
+
+#[test]
+fn test_caverns_stay_in_their_layers() {
+    let layer = CavernLayer { z: -60, height: 8 };
+    let terrain = Terrain::new(&WorldGenerationSettings {
+        cave_density: 0.0,
+        caverns: vec![layer.clone()],
+        ..default()
+    });
+    // the floors are shifted by up to four blocks
+    let layer_z = layer.z - 4..layer.z + 4 + layer.height as i32;
+    let mut hollow = 0;
+    for x in (0..256).step_by(4) {
+        for y in (0..256).step_by(4) {
+            for z in -100..0 {
+                if terrain.is_cavern(IVec3::new(x, y, z)) {
+                    assert!(layer_z.contains(&z), "{x} {y} {z}");
+                    hollow += 1;
+                }
+            }
+        }
+    }
+    assert!(hollow > 0);
+}
+
+#[test]
+fn test_climate_effects() {
+    let climate = |temperature, rainfall| {
+        Terrain::new(&WorldGenerationSettings {
+            biomes: BiomeSettings {
+                temperature,
+                rainfall,
+                ..default()
+            },
+            ..default()
+        })
+    };
+    let (origin, size) = (IVec2::splat(-256), IVec2::splat(512));
+
+    let tundra = climate(-1.0, 0.0);
+    let columns = tundra.sample_columns(origin, size / 8, 8);
+    assert!(columns.iter().all(|column| column.biome == Biome::Tundra));
+
+    // lakes dry out in deserts
+    let desert = climate(1.0, -1.0);
+    let surface_water = desert.hydrology.surface_water(&desert, origin, size);
+    let mut lakes = 0;
+    for column in desert.columns(origin, size) {
+        let lake = matches!(
+            surface_water.get(&column.coordinates),
+            Some(SurfaceWater::Lake { .. })
+        );
+        if lake && column.biome == Biome::Desert && !desert.is_ocean(column.coordinates) {
+            assert_eq!(column.water_level, None, "{}", column.coordinates);
+            lakes += 1;
+        }
+    }
+    assert!(lakes > 0);
+
+    // pools form on dry ground in swamps
+    let swamp = climate(0.0, 1.0);
+    let pools = swamp
+        .columns(origin, size)
+        .iter()
+        .filter(|column| {
+            column.biome == Biome::Swamp
+                && column.water_level == Some(column.surface + 1)
+                && !swamp.is_ocean(column.coordinates)
+        })
+        .count();
+    assert!(pools > 0);
+}
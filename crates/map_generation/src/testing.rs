@@ -0,0 +1,62 @@
+//! Fixtures for tests that need a [`WorldMap`]. Other crates get them through the `testing`
+//! feature.
+
+use std::ops::Range;
+
+use bevy::prelude::*;
+use common::{coordinates::IWorldCoordinates, resources::MapBounds};
+
+use crate::{
+    block_type::{BlockType, SolidMaterial},
+    map_generation::WorldGenerationSettings,
+    world_map::WorldMap,
+};
+
+/// What the fixtures are built from.
+pub const STONE: BlockType = BlockType::Solid(SolidMaterial::Granite);
+
+/// The layer floors are laid on. Nothing is generated this high up, so entities walk on the layer
+/// above without anything else in the way.
+pub const FLOOR: i32 = 500;
+
+/// Returns an unmodified world map with the default settings.
+pub fn world_map() -> WorldMap {
+    WorldMap::new(
+        Entity::PLACEHOLDER,
+        &WorldGenerationSettings::default(),
+        MapBounds::default(),
+    )
+}
+
+/// Returns a world map with a floor of [`STONE`] on [`FLOOR`], spanning `x` and `y`.
+pub fn floor(x: Range<i32>, y: Range<i32>) -> WorldMap {
+    let mut world_map = world_map();
+    for x in x {
+        for y in y.clone() {
+            world_map.set_block(IWorldCoordinates(IVec3::new(x, y, FLOOR)), STONE);
+        }
+    }
+    world_map
+}
+
+/// Builds a wall on the floor, two blocks high so it can't be stepped onto.
+pub fn wall(world_map: &mut WorldMap, columns: impl IntoIterator<Item = IVec2>) {
+    for column in columns {
+        for z in FLOOR + 1..FLOOR + 3 {
+            world_map.set_block(IWorldCoordinates(column.extend(z)), STONE);
+        }
+    }
+}
+
+/// Walls in the columns along the edges of `area`, so nothing gets in or out.
+pub fn room(world_map: &mut WorldMap, area: IRect) {
+    let columns = (area.min.x..=area.max.x)
+        .flat_map(|x| (area.min.y..=area.max.y).map(move |y| IVec2::new(x, y)))
+        .filter(|column| {
+            column.x == area.min.x
+                || column.x == area.max.x
+                || column.y == area.min.y
+                || column.y == area.max.y
+        });
+    wall(world_map, columns);
+}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TileTextureIndex;
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::Column;
+
+/// Trees can reach this many blocks sideways from their trunk, so chunks have to look this far
+/// beyond their borders.
+pub(crate) const MAX_CANOPY_RADIUS: i32 = 2;
+/// The highest a tree can grow above the surface, including its canopy.
+pub(crate) const MAX_TREE_HEIGHT: i32 = 10;
+
+/// Anything growing on the surface.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
+pub enum Vegetation {
+    /// Trunks block the way and are the source of wood.
+    Trunk(Tree),
+    /// The leaves of a tree. Can be passed through, but not stood on.
+    Canopy(Tree),
+    Shrub,
+    /// Plants that can be harvested.
+    Plant(Plant),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
+pub enum Tree {
+    Oak,
+    Pine,
+    Willow,
+    Palm,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
+pub enum Plant {
+    Berries,
+    Herbs,
+    Reeds,
+    Cactus,
+}
+
+impl Vegetation {
+    pub(crate) fn color(&self) -> Color {
+        match self {
+            Vegetation::Trunk(Tree::Palm) => Color::srgb_u8(165, 130, 85),
+            Vegetation::Trunk(Tree::Pine) => Color::srgb_u8(95, 65, 45),
+            Vegetation::Trunk(_) => Color::srgb_u8(120, 85, 55),
+            Vegetation::Canopy(Tree::Oak) => Color::srgb_u8(70, 140, 60),
+            Vegetation::Canopy(Tree::Pine) => Color::srgb_u8(40, 100, 70),
+            Vegetation::Canopy(Tree::Willow) => Color::srgb_u8(120, 160, 80),
+            Vegetation::Canopy(Tree::Palm) => Color::srgb_u8(110, 170, 70),
+            Vegetation::Shrub => Color::srgb_u8(90, 130, 60),
+            Vegetation::Plant(Plant::Berries) => Color::srgb_u8(170, 60, 90),
+            Vegetation::Plant(Plant::Herbs) => Color::srgb_u8(150, 190, 90),
+            Vegetation::Plant(Plant::Reeds) => Color::srgb_u8(160, 150, 80),
+            Vegetation::Plant(Plant::Cactus) => Color::srgb_u8(80, 150, 90),
+        }
+    }
+
+    pub(crate) fn tile_texture_index(&self) -> TileTextureIndex {
+        match self {
+            Vegetation::Trunk(_) => TileTextureIndex(0),
+            _ => TileTextureIndex(1),
+        }
+    }
+
+    /// Returns how much damage the vegetation takes before it is destroyed, see
+    /// [`crate::block_type::SolidMaterial::durability`].
+    pub const fn durability(&self) -> f32 {
+        match self {
+            Vegetation::Trunk(_) => 2.0,
+            Vegetation::Canopy(_) | Vegetation::Shrub | Vegetation::Plant(_) => 0.25,
+        }
+    }
+
+    /// Returns true if the vegetation hides everything below it.
+    pub(crate) fn is_opaque(&self) -> bool {
+        matches!(self, Vegetation::Trunk(_) | Vegetation::Canopy(_))
+    }
+}
+
+impl Tree {
+    /// Returns the height of the trunk, `roll` is a random number between 0.0 and 1.0.
+    fn trunk_height(&self, roll: f64) -> i32 {
+        let (min, max) = match self {
+            Tree::Oak => (4, 6),
+            Tree::Pine => (5, 8),
+            Tree::Willow => (3, 5),
+            Tree::Palm => (4, 6),
+        };
+        min + (roll * (max - min + 1) as f64) as i32
+    }
+
+    /// Returns the offsets of all canopy blocks from the top of the trunk.
+    fn canopy(&self) -> impl Iterator<Item = IVec3> {
+        let (layers, radius) = match self {
+            Tree::Oak | Tree::Willow => (-1..=1, MAX_CANOPY_RADIUS),
+            Tree::Pine => (-2..=1, MAX_CANOPY_RADIUS),
+            Tree::Palm => (0..=0, MAX_CANOPY_RADIUS),
+        };
+        let shape = *self;
+        layers.flat_map(move |z| {
+            let layer_radius = match shape {
+                // pines get narrower to the top
+                Tree::Pine => (radius - (z + 2) / 2).max(0),
+                _ if z == 0 => radius,
+                _ => radius - 1,
+            };
+            (-layer_radius..=layer_radius).flat_map(move |x| {
+                (-layer_radius..=layer_radius)
+                    .filter(move |y| x * x + y * y <= layer_radius * layer_radius + 1)
+                    .map(move |y| IVec3::new(x, y, z))
+            })
+        })
+    }
+}
+
+/// Returns everything growing on a column, with its world coordinates.
+///
+/// `rolls` are three random numbers between 0.0 and 1.0, that have to be the same whenever the
+/// column is generated.
+pub(crate) fn grow(column: &Column, density: f64, rolls: [f64; 3]) -> Vec<(IVec3, Vegetation)> {
+    let biome = column.biome;
+    if column.water_level.is_some() || rolls[0] >= biome.vegetation_density() * density {
+        return vec![];
+    }
+    let ground = column.coordinates.extend(column.surface + 1);
+    let shrub_share = (1.0 - biome.tree_share()) / 2.0;
+    if rolls[1] < biome.tree_share() {
+        let tree = biome.tree();
+        let height = tree.trunk_height(rolls[2]);
+        let top = ground + IVec3::Z * (height - 1);
+        let trunk = (0..height).map(|z| (ground + IVec3::Z * z, Vegetation::Trunk(tree)));
+        let canopy = tree
+            .canopy()
+            .map(|offset| top + offset)
+            // the canopy doesn't replace the trunk
+            .filter(|position| position.truncate() != ground.truncate() || position.z > top.z)
+            .map(|position| (position, Vegetation::Canopy(tree)));
+        trunk.chain(canopy).collect()
+    } else if rolls[1] < biome.tree_share() + shrub_share {
+        vec![(ground, Vegetation::Shrub)]
+    } else {
+        vec![(ground, Vegetation::Plant(biome.plant()))]
+    }
+}
+
+#[test]
+fn test_tree_fits_margins() {
+    use crate::biome::Biome;
+
+    for biome in [Biome::Forest, Biome::Tundra, Biome::Swamp, Biome::Desert] {
+        let column = Column {
+            coordinates: IVec2::ZERO,
+            surface: 0,
+            water_level: None,
+            biome,
+        };
+        let tree = grow(&column, 1.0, [0.0, 0.0, 0.99]);
+        assert!(tree.len() > 1, "{:?} should grow a tree", biome);
+        for (position, _) in tree {
+            assert!(position.z > column.surface);
+            assert!(position.z <= column.surface + MAX_TREE_HEIGHT);
+            assert!(position.x.abs() <= MAX_CANOPY_RADIUS);
+            assert!(position.y.abs() <= MAX_CANOPY_RADIUS);
+        }
+    }
+}
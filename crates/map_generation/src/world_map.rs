@@ -1,55 +1,231 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use bevy::prelude::*;
 use common::{
+    coordinates::{ChunkCoordinates, IWorldCoordinates},
+    resources::MapBounds,
     traits::Neighbors,
-    types::{ChunkCoordinates, IWorldCoordinates},
 };
-use noise::OpenSimplex;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     block_type::BlockType,
-    chunk::{Chunk, ToChunkAndBlock, to_index},
+    chunk::{Chunk, ChunkDelta, to_index},
+    map_generation::WorldGenerationSettings,
+    terrain::{Column, Terrain},
 };
 
-#[derive(Resource, Reflect)]
+/// Cloning the world map is cheap, everything is shared between the clones until one of them
+/// changes. Even then, only the table that changed is copied, while the chunks and deltas in it
+/// stay shared. This way, a clone can be read on another thread while the game goes on, and the
+/// chunks it generates can be [merged](Self::merge) back.
+#[derive(Clone, Resource, Reflect)]
 #[reflect(Resource)]
 pub struct WorldMap {
-    chunks: HashMap<IVec3, Chunk>,
+    /// Chunks currently held in memory. Any of them can be dropped and regenerated from the seed
+    /// and [`Self::deltas`].
+    #[reflect(ignore)]
+    chunks: Arc<HashMap<IVec3, Arc<Chunk>>>,
+    /// Modifications of chunks, the only chunk data that can't be regenerated.
+    #[reflect(ignore)]
+    deltas: Arc<HashMap<IVec3, Arc<ChunkDelta>>>,
     #[reflect(ignore)]
-    noise: OpenSimplex,
+    terrain: Arc<Terrain>,
     pub(crate) entity: Entity,
-    block_states: HashMap<IVec3, f32>,
+    #[reflect(ignore)]
+    block_states: Arc<HashMap<IVec3, f32>>,
+    bounds: MapBounds,
+}
+
+/// Everything about a [`WorldMap`] that can't be regenerated from the seed.
+///
+/// When inserted as a resource before entering [`common::states::AppState::MainGame`],
+/// the world map will be restored from it instead of starting fresh.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Resource, Serialize)]
+pub struct WorldMapSave {
+    deltas: Vec<(IVec3, ChunkDelta)>,
+    block_states: Vec<(IVec3, f32)>,
+    #[serde(default)]
+    bounds: MapBounds,
 }
 
 impl WorldMap {
-    pub(crate) fn new(entity: Entity, seed: u32) -> Self {
+    /// Creates a world map that hasn't been modified yet. Chunks are generated lazily.
+    pub fn new(entity: Entity, settings: &WorldGenerationSettings, bounds: MapBounds) -> Self {
         WorldMap {
-            chunks: HashMap::default(),
-            noise: OpenSimplex::new(seed),
+            chunks: Arc::default(),
+            deltas: Arc::default(),
+            terrain: Arc::new(Terrain::new(settings)),
             entity,
-            block_states: HashMap::default(),
+            block_states: Arc::default(),
+            bounds,
+        }
+    }
+
+    /// Restores a world map from a save. Chunks are regenerated lazily, with the saved deltas applied.
+    pub(crate) fn from_save(
+        entity: Entity,
+        settings: &WorldGenerationSettings,
+        save: &WorldMapSave,
+    ) -> Self {
+        let mut world_map = WorldMap::new(entity, settings, save.bounds);
+        world_map.deltas = Arc::new(
+            save.deltas
+                .iter()
+                .map(|(coordinates, delta)| (*coordinates, Arc::new(delta.clone())))
+                .collect(),
+        );
+        world_map.block_states = Arc::new(save.block_states.iter().copied().collect());
+        world_map
+    }
+
+    /// Returns the dry spot closest to the center of the embark site, right above the surface.
+    /// Falls back to the center if the whole site is under water.
+    pub(crate) fn embark_position(&self, site: IRect) -> IWorldCoordinates {
+        let center = site.center();
+        let columns = self.terrain.columns(site.min, site.size());
+        let distance = |column: &&Column| column.coordinates.distance_squared(center);
+        columns
+            .iter()
+            .filter(|column| column.water_level.is_none())
+            // trees would be in the way
+            .filter(|column| self.terrain.vegetation(column).is_empty())
+            .min_by_key(distance)
+            .or_else(|| columns.iter().min_by_key(distance))
+            .map(|column| IWorldCoordinates(column.coordinates.extend(column.surface + 1)))
+            .unwrap_or(IWorldCoordinates(center.extend(0)))
+    }
+
+    /// Collects all chunk deltas and block states, so they can be written to disk.
+    pub fn save(&self) -> WorldMapSave {
+        WorldMapSave {
+            deltas: self
+                .deltas
+                .iter()
+                .map(|(coordinates, delta)| (*coordinates, ChunkDelta::clone(delta)))
+                .collect(),
+            block_states: self
+                .block_states
+                .iter()
+                .map(|(coordinates, health)| (*coordinates, *health))
+                .collect(),
+            bounds: self.bounds,
         }
     }
 
     /// Checks every surrounding chunk. If it doesn't exist, it will be created.
-    pub(crate) fn ensure_surrounding_exist(&mut self, coordinates: ChunkCoordinates) {
-        self.get_or_insert_chunk_mut(coordinates);
+    pub fn ensure_surrounding_exist(&mut self, coordinates: ChunkCoordinates) {
+        self.ensure_exists(coordinates);
         for (neighbor, _) in coordinates.0.all_neighbors() {
-            self.get_or_insert_chunk_mut(ChunkCoordinates(neighbor));
+            self.ensure_exists(ChunkCoordinates(neighbor));
+        }
+    }
+
+    /// Like [`Self::ensure_surrounding_exist`], but generates no more than a single chunk, so the
+    /// work can be spread out. Returns false if none was missing.
+    pub fn generate_surrounding(&mut self, coordinates: ChunkCoordinates) -> bool {
+        let missing = surrounding(coordinates).find(|chunk| !self.chunks.contains_key(chunk));
+        if let Some(missing) = missing {
+            self.ensure_exists(ChunkCoordinates(missing));
+        }
+        missing.is_some()
+    }
+
+    /// Returns true if the chunk and every chunk around it are in memory.
+    pub fn is_surrounding_resident(&self, coordinates: ChunkCoordinates) -> bool {
+        surrounding(coordinates).all(|chunk| self.chunks.contains_key(&chunk))
+    }
+
+    /// (Re)generates a chunk, if it isn't in memory.
+    fn ensure_exists(&mut self, coordinates: ChunkCoordinates) {
+        if !self.chunks.contains_key(&coordinates.0) {
+            let chunk = Chunk::with_delta(
+                coordinates,
+                &self.terrain,
+                self.deltas.get(&coordinates.0).map(Arc::as_ref),
+            );
+            Arc::make_mut(&mut self.chunks).insert(coordinates.0, Arc::new(chunk));
         }
     }
 
-    /// Returns a chunk for a given coordinate. Will create a new one, if none has been created thus far.
+    /// Returns a chunk for a given coordinate. Will (re)generate it, if it isn't in memory.
     fn get_or_insert_chunk_mut(&mut self, coordinates: ChunkCoordinates) -> &mut Chunk {
-        self.chunks
-            .entry(coordinates.0)
-            .or_insert(Chunk::new(coordinates, self.noise))
+        self.ensure_exists(coordinates);
+        let chunk = Arc::make_mut(&mut self.chunks)
+            .get_mut(&coordinates.0)
+            .unwrap();
+        // copies the chunk if a clone of the map still shares it
+        Arc::make_mut(chunk)
+    }
+
+    /// Returns the part of the world that can be played in.
+    pub fn bounds(&self) -> MapBounds {
+        self.bounds
+    }
+
+    /// Returns true if the chunk differs from the generated one.
+    pub fn is_modified(&self, coordinates: ChunkCoordinates) -> bool {
+        self.deltas.contains_key(&coordinates.0)
+    }
+
+    /// Returns how many chunks are currently held in memory.
+    pub fn resident_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Drops chunks that aren't contained in `keep`, farthest from `center` first, until no more
+    /// than `budget` chunks are left. They will be regenerated when they are needed again.
+    ///
+    /// Returns the number of evicted chunks.
+    pub(crate) fn evict(&mut self, keep: &HashSet<IVec3>, budget: usize, center: IVec3) -> usize {
+        let mut candidates: Vec<IVec3> = self
+            .chunks
+            .keys()
+            .filter(|coordinates| !keep.contains(*coordinates))
+            .copied()
+            .collect();
+        let excess = self
+            .chunks
+            .len()
+            .saturating_sub(budget)
+            .min(candidates.len());
+        candidates.sort_by_key(|coordinates| Reverse(coordinates.distance_squared(center)));
+        for coordinates in candidates.iter().take(excess) {
+            Arc::make_mut(&mut self.chunks).remove(coordinates);
+        }
+        excess
+    }
+
+    /// Adds the chunks a clone has generated, so they don't have to be generated again. Chunks that
+    /// are in memory already or were modified after the clone was made are skipped.
+    pub fn merge(&mut self, clone: WorldMap) {
+        if Arc::ptr_eq(&self.chunks, &clone.chunks) {
+            return;
+        }
+        for (coordinates, chunk) in clone.chunks.iter() {
+            let unchanged = match (self.deltas.get(coordinates), clone.deltas.get(coordinates)) {
+                (None, None) => true,
+                (Some(delta), Some(cloned)) => Arc::ptr_eq(delta, cloned),
+                _ => false,
+            };
+            if unchanged && !self.chunks.contains_key(coordinates) {
+                Arc::make_mut(&mut self.chunks).insert(*coordinates, chunk.clone());
+            }
+        }
     }
 
     /// Returns an option of type BlockType, if the corresponding chunk has been
     /// found. Returns None when the chunk is not loaded.
+    ///
+    /// Beyond the map edge, every block is empty.
     pub fn get_block(&self, coordinates: IWorldCoordinates) -> Option<BlockType> {
+        if !self.bounds.contains(coordinates.0) {
+            return Some(BlockType::None);
+        }
         let (chunk_coordinate, block_coordinates) = coordinates.to_chunk_and_block();
         let index = to_index(block_coordinates);
         self.chunks
@@ -57,7 +233,12 @@ impl WorldMap {
             .map(|chunk| chunk.blocks[index])
     }
 
+    /// Returns true if the block is solid or its chunk isn't loaded. Nothing beyond the map edge
+    /// is solid.
     pub fn solidness(&self, coordinates: IWorldCoordinates) -> bool {
+        if !self.bounds.contains(coordinates.0) {
+            return false;
+        }
         let (chunk_coordinates, block_coordinates) = coordinates.to_chunk_and_block();
         let index = to_index(block_coordinates);
         self.chunks
@@ -65,20 +246,238 @@ impl WorldMap {
             .is_none_or(|chunk| chunk.blocks[index].is_solid())
     }
 
-    /// Adds damage to a block. Returns true, if the block is destroyed, false otherwise.
+    /// Adds damage to a block. Every block starts with its [`BlockType::durability`] as health and
+    /// is destroyed once it reaches zero. Returns true, if the block is destroyed, false otherwise.
     pub fn damage_block(&mut self, coordinates: IWorldCoordinates, damage: f32) -> bool {
+        if !self.bounds.contains(coordinates.0) {
+            return false;
+        }
+        self.ensure_exists(coordinates.chunk());
+        let Some(durability) = self
+            .get_block(coordinates)
+            .and_then(|block| block.durability())
+        else {
+            return false;
+        };
         let remaining_health = {
-            *self
-                .block_states
+            *Arc::make_mut(&mut self.block_states)
                 .entry(coordinates.0)
-                .and_modify(|block| *block -= damage)
-                .or_insert(1.0 - damage)
+                .and_modify(|health| *health -= damage)
+                .or_insert(durability - damage)
         };
-        if remaining_health <= 0.0 {
-            let (chunk_coordinates, block_coordinates) = coordinates.to_chunk_and_block();
-            self.get_or_insert_chunk_mut(chunk_coordinates)
-                .remove_block(block_coordinates);
+        let destroyed = remaining_health <= 0.0;
+        if destroyed {
+            // also forgets the health of the block
+            self.set_block(coordinates, BlockType::None);
+        }
+        destroyed
+    }
+
+    /// Replaces a block, whatever was there before. Returns false if the coordinates are beyond
+    /// the map edge.
+    pub fn set_block(&mut self, coordinates: IWorldCoordinates, block: BlockType) -> bool {
+        if !self.bounds.contains(coordinates.0) {
+            return false;
+        }
+        let (chunk_coordinates, block_coordinates) = coordinates.to_chunk_and_block();
+        Arc::make_mut(
+            Arc::make_mut(&mut self.deltas)
+                .entry(chunk_coordinates.0)
+                .or_default(),
+        )
+        .set(block_coordinates, block);
+        self.get_or_insert_chunk_mut(chunk_coordinates)
+            .set_block(block_coordinates, block);
+        // a new block starts out undamaged
+        if self.block_states.contains_key(&coordinates.0) {
+            Arc::make_mut(&mut self.block_states).remove(&coordinates.0);
         }
-        remaining_health < 0.0
+        true
+    }
+
+    /// Places a block, but only where there is nothing yet. Returns true if the block was placed.
+    pub fn place_block(&mut self, coordinates: IWorldCoordinates, block: BlockType) -> bool {
+        if !self.bounds.contains(coordinates.0) {
+            return false;
+        }
+        self.ensure_exists(coordinates.chunk());
+        self.get_block(coordinates) == Some(BlockType::None) && self.set_block(coordinates, block)
+    }
+}
+
+/// Returns a chunk and the chunks around it.
+fn surrounding(coordinates: ChunkCoordinates) -> impl Iterator<Item = IVec3> {
+    std::iter::once(coordinates.0).chain(
+        coordinates
+            .0
+            .all_neighbors()
+            .into_iter()
+            .map(|(neighbor, _)| neighbor),
+    )
+}
+
+#[test]
+fn test_beyond_edge_is_empty() {
+    let bounds = MapBounds {
+        min: IVec3::splat(-8),
+        max: IVec3::splat(7),
+    };
+    let mut world_map = WorldMap::new(
+        Entity::PLACEHOLDER,
+        &WorldGenerationSettings::default(),
+        bounds,
+    );
+    let outside = IWorldCoordinates(IVec3::new(8, 0, 0));
+    world_map.ensure_surrounding_exist(outside.to_chunk_and_block().0);
+    assert_eq!(world_map.get_block(outside), Some(BlockType::None));
+    assert!(!world_map.solidness(outside));
+    assert!(!world_map.damage_block(outside, 1.0));
+}
+
+#[test]
+fn test_place_block() {
+    let mut world_map = crate::testing::world_map();
+    // high above the ground, where nothing is generated
+    let coordinates = IWorldCoordinates(IVec3::new(0, 0, 500));
+    let block = BlockType::Solid(crate::block_type::SolidMaterial::Granite);
+    assert!(world_map.place_block(coordinates, block));
+    assert_eq!(world_map.get_block(coordinates), Some(block));
+    assert!(!world_map.place_block(coordinates, BlockType::Liquid));
+    assert!(world_map.is_modified(coordinates.to_chunk_and_block().0));
+}
+
+#[test]
+fn test_durability() {
+    use crate::block_type::SolidMaterial;
+
+    let mut world_map = crate::testing::world_map();
+    let dirt = IWorldCoordinates(IVec3::new(0, 0, 500));
+    let granite = IWorldCoordinates(IVec3::new(1, 0, 500));
+    world_map.place_block(dirt, BlockType::Solid(SolidMaterial::Dirt));
+    world_map.place_block(granite, BlockType::Solid(SolidMaterial::Granite));
+    for _ in 0..3 {
+        assert!(!world_map.damage_block(dirt, 0.25));
+        assert!(!world_map.damage_block(granite, 0.25));
     }
+    assert!(world_map.damage_block(dirt, 0.25));
+    assert!(!world_map.damage_block(granite, 0.25));
+    assert_eq!(world_map.get_block(dirt), Some(BlockType::None));
+    assert!(!world_map.block_states.contains_key(&dirt.0));
+    // empty blocks can't be damaged
+    assert!(!world_map.damage_block(dirt, 0.25));
+    assert!(!world_map.block_states.contains_key(&dirt.0));
+}
+
+#[test]
+fn test_damage_to_zero_destroys() {
+    use crate::block_type::SolidMaterial;
+
+    let mut world_map = crate::testing::world_map();
+    let coordinates = IWorldCoordinates(IVec3::new(0, 0, 500));
+    let block = BlockType::Solid(SolidMaterial::Dirt);
+    world_map.place_block(coordinates, block);
+    // the block is removed exactly when the damage is reported as destroying it
+    assert!(world_map.damage_block(coordinates, block.durability().unwrap()));
+    assert_eq!(world_map.get_block(coordinates), Some(BlockType::None));
+}
+
+#[test]
+fn test_save_round_trip() {
+    use crate::testing::STONE;
+
+    let mut world_map = crate::testing::world_map();
+    let placed = IWorldCoordinates(IVec3::new(3, 4, 500));
+    let damaged = IWorldCoordinates(IVec3::new(-20, 4, 500));
+    world_map.place_block(placed, STONE);
+    world_map.place_block(damaged, STONE);
+    world_map.damage_block(damaged, 0.5);
+
+    let mut restored = WorldMap::from_save(
+        Entity::PLACEHOLDER,
+        &WorldGenerationSettings::default(),
+        &world_map.save(),
+    );
+    assert_eq!(restored.deltas, world_map.deltas);
+    assert_eq!(restored.block_states, world_map.block_states);
+    assert_eq!(restored.bounds(), world_map.bounds());
+    // chunks are regenerated with the deltas applied, and damage carries over
+    assert_eq!(restored.resident_chunks(), 0);
+    restored.ensure_surrounding_exist(placed.chunk());
+    assert_eq!(restored.get_block(placed), Some(STONE));
+    let remaining = STONE.durability().unwrap() - 0.5;
+    assert!(!restored.damage_block(damaged, remaining - 0.1));
+    assert!(restored.damage_block(damaged, 0.1));
+}
+
+#[test]
+fn test_evict() {
+    use crate::{chunk::CHUNK_SIZE, testing::STONE};
+
+    let mut world_map = crate::testing::world_map();
+    // a row of eight chunks high above the ground, each with a block placed in it
+    let blocks: Vec<IWorldCoordinates> = (0..8)
+        .map(|x| IWorldCoordinates(IVec3::new(x * CHUNK_SIZE.x as i32, 0, 500)))
+        .collect();
+    for block in &blocks {
+        world_map.place_block(*block, STONE);
+    }
+    let resident = |world_map: &WorldMap| {
+        blocks
+            .iter()
+            .map(|block| world_map.get_block(*block).is_some())
+            .collect::<Vec<_>>()
+    };
+    let camera = blocks[0].chunk().0;
+
+    // the farthest chunks from the camera go first, unless they are kept
+    let keep = HashSet::from([blocks[7].chunk().0]);
+    assert_eq!(world_map.evict(&keep, 5, camera), 3);
+    assert_eq!(world_map.resident_chunks(), 5);
+    assert_eq!(
+        resident(&world_map),
+        [true, true, true, true, false, false, false, true]
+    );
+    assert_eq!(world_map.evict(&keep, 5, camera), 0);
+
+    // kept chunks stay, even over budget
+    let keep: HashSet<IVec3> = [0, 1, 2, 3, 7]
+        .into_iter()
+        .map(|index| blocks[index].chunk().0)
+        .collect();
+    assert_eq!(world_map.evict(&keep, 0, camera), 0);
+    assert_eq!(world_map.resident_chunks(), 5);
+
+    // evicted chunks come back with their changes
+    world_map.place_block(blocks[5], BlockType::Liquid);
+    assert_eq!(world_map.get_block(blocks[5]), Some(STONE));
+}
+
+#[test]
+fn test_merge() {
+    use crate::testing::STONE;
+
+    let mut world_map = crate::testing::world_map();
+    let clone_of = |world_map: &WorldMap, blocks: &[IWorldCoordinates]| {
+        let mut clone = world_map.clone();
+        for block in blocks {
+            clone.ensure_surrounding_exist(block.chunk());
+        }
+        clone
+    };
+    let generated = IWorldCoordinates(IVec3::new(0, 0, 500));
+    let changed = IWorldCoordinates(IVec3::new(100, 0, 500));
+    let clone = clone_of(&world_map, &[generated, changed]);
+
+    // the original changes a chunk and drops it again, while the clone still has the old one
+    world_map.place_block(changed, STONE);
+    world_map.evict(&HashSet::new(), 0, IVec3::ZERO);
+    world_map.merge(clone);
+    assert_eq!(world_map.get_block(generated), Some(BlockType::None));
+    assert_eq!(world_map.get_block(changed), None);
+    assert_eq!(world_map.resident_chunks(), 27 * 2 - 1);
+
+    // chunks generated after the change are fine
+    let clone = clone_of(&world_map, &[changed]);
+    world_map.merge(clone);
+    assert_eq!(world_map.get_block(changed), Some(STONE));
 }
@@ -4,6 +4,7 @@ use common::{
     states::AppState,
     traits::{SpawnNamedObserver, UiRoot},
 };
+use save_game::LoadGame;
 
 pub fn plugin(app: &mut App) {
     app.add_systems(OnEnter(AppState::MainMenu), setup);
@@ -22,6 +23,10 @@ fn setup(background: Res<MenuBackgroundAsset>, mut commands: Commands) {
         .with_children(|root| {
             let target = root.spawn(ui::UiButton::menu("START")).id();
             root.spawn_named_observer(target, on_press_start, "on_press_start");
+            if save_game::save_exists() {
+                let target = root.spawn(ui::UiButton::menu("CONTINUE")).id();
+                root.spawn_named_observer(target, on_press_continue, "on_press_continue");
+            }
         });
 
     commands.spawn((Camera2d, DespawnOnExit(AppState::MainMenu)));
@@ -30,3 +35,7 @@ fn setup(background: Res<MenuBackgroundAsset>, mut commands: Commands) {
 fn on_press_start(_trigger: On<Pointer<Click>>, mut next_state: ResMut<NextState<AppState>>) {
     next_state.set(AppState::WorldGeneration);
 }
+
+fn on_press_continue(_trigger: On<Pointer<Click>>, mut commands: Commands) {
+    commands.trigger(LoadGame);
+}
@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+
+/// How many nodes all pathfinders together may expand per frame.
+///
+/// Every frame, the budget is shared between the pathfinders that aren't searching already,
+/// weighed by their [`PathPriority`]. Each of them then expands its share on another thread.
+/// Short paths are therefore found within a single batch, while long searches are spread over
+/// several.
+///
+/// Every step a search spends generating, labelling or connecting a chunk counts as [`CHUNK_COST`]
+/// expansions. A search that spends more than its share has less left in its next batch. Chunks
+/// whose regions are labelled again after the map changed are paid for first.
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct PathfindingBudget {
+    pub expansions_per_frame: u32,
+}
+
+/// How many node expansions a single step of preparing a chunk for a search is worth.
+pub const CHUNK_COST: u32 = 16;
+
+impl Default for PathfindingBudget {
+    fn default() -> Self {
+        PathfindingBudget {
+            expansions_per_frame: 256,
+        }
+    }
+}
+
+/// How much of the [`PathfindingBudget`] the pathfinders of an entity get.
+///
+/// Attach this to the entity the pathfinders are inserted on, like a dwarf. Workers have it.
+/// Entities without it are [`PathPriority::Normal`]. While a blocked path is replaced, the
+/// priority is raised, since the entity waits for the new one.
+#[derive(Clone, Component, Copy, Debug, Default, Eq, PartialEq, Reflect)]
+#[reflect(Component)]
+pub enum PathPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl PathPriority {
+    /// Returns how much of the budget a pathfinder gets, relative to the others.
+    pub const fn weight(&self) -> u32 {
+        match self {
+            PathPriority::Low => 1,
+            PathPriority::Normal => 2,
+            PathPriority::High => 4,
+        }
+    }
+
+    /// Returns the next higher priority.
+    pub const fn raised(&self) -> Self {
+        match self {
+            PathPriority::Low => PathPriority::Normal,
+            PathPriority::Normal | PathPriority::High => PathPriority::High,
+        }
+    }
+}
+
+/// What the pathfinding has been up to, to keep an eye on it in the inspector.
+#[derive(Default, Reflect, Resource)]
+#[reflect(Resource)]
+pub struct PathfindingStats {
+    /// Pathfinders that are still searching.
+    pub pending: usize,
+    /// Node expansions handed out during the last frame.
+    pub expansions: u32,
+    /// Searches that found a path.
+    pub completed: u32,
+    /// Searches that failed.
+    pub failed: u32,
+    /// Chunks in the [`crate::ChunkGraph`].
+    pub graph_chunks: usize,
+    /// Chunks whose regions were labelled again during the last frame.
+    pub relabelled: usize,
+}
@@ -0,0 +1,511 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    sync::Arc,
+};
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use common::{
+    constants::CHUNK_SIZE,
+    coordinates::{ChunkCoordinates, IWorldCoordinates},
+    traits::{Adjacency, Connectivity, Neighbors},
+};
+use map_generation::{messages::BlockUpdate, movement::distance, world_map::WorldMap};
+use priority_queue::PriorityQueue;
+
+use crate::{
+    budget::{CHUNK_COST, PathfindingBudget, PathfindingStats},
+    pathfinding_map::PathfindingMap,
+    regions::Regions,
+};
+
+/// How many blocks wide an entrance has to be to keep the portals at both of its ends.
+const LONG_ENTRANCE: usize = 6;
+
+/// The abstract graph of the hierarchical pathfinding (HPA*), built on top of the chunks.
+///
+/// Wherever entities can move from one chunk into another, both blocks are portals. Neighboring
+/// portals along a border form an entrance. Of short entrances, only the portal closest to their
+/// middle is kept, of long ones those at both ends, so paths crossing near an end aren't led
+/// through the middle. Portals are connected to their counterpart across the border and, by paths
+/// that stay within their chunk, to the other portals of the chunk.
+///
+/// Chunks are added the first time a search reaches them and are dropped as soon as a block in or
+/// next to them changes.
+///
+/// The graph also holds the [`Regions`] of the chunks, to tell quickly when nothing can be reached.
+///
+/// Clones share their tables like those of the [`WorldMap`] do. Searches running on other threads
+/// work on a clone and [`ChunkGraph::merge`] the chunks they added back in.
+#[derive(Clone, Default, Resource)]
+pub struct ChunkGraph {
+    chunks: Arc<HashMap<IVec3, Arc<ChunkNodes>>>,
+    regions: Regions,
+    /// Counts the invalidations, so chunks built by a clone can be told apart from stale ones.
+    generation: u64,
+    /// The generation in which every chunk was last dropped.
+    invalidated: Arc<HashMap<IVec3, u64>>,
+}
+
+/// The portals of a single chunk.
+#[derive(Default)]
+struct ChunkNodes {
+    /// Every portal of the chunk with the edges leaving it.
+    edges: HashMap<IVec3, Vec<Edge>>,
+}
+
+/// A connection between two nodes of the graph.
+#[derive(Clone, Debug)]
+pub(crate) struct Edge {
+    pub(crate) to: IVec3,
+    pub(crate) cost: f32,
+    /// The blocks leading to `to`, including it but not the block the edge starts at.
+    pub(crate) path: Vec<IVec3>,
+}
+
+/// A cost that can be ordered, so it can be used as a priority.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Cost(pub(crate) f32);
+
+impl Eq for Cost {}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl ChunkGraph {
+    /// Returns the edges leaving a block. Blocks that aren't portals have none.
+    pub(crate) fn edges(&mut self, world_map: &mut WorldMap, coordinates: IVec3) -> &[Edge] {
+        let chunk = IWorldCoordinates(coordinates).chunk();
+        self.nodes(world_map, chunk)
+            .edges
+            .get(&coordinates)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the portals of a chunk.
+    pub(crate) fn portals(
+        &mut self,
+        world_map: &mut WorldMap,
+        chunk: ChunkCoordinates,
+    ) -> Vec<IVec3> {
+        self.nodes(world_map, chunk).edges.keys().copied().collect()
+    }
+
+    /// Returns how many chunks are part of the graph.
+    pub fn chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the connected regions of the chunks labelled so far.
+    pub fn regions(&self) -> &Regions {
+        &self.regions
+    }
+
+    /// Returns the connected regions, to label more of them.
+    pub fn regions_mut(&mut self) -> &mut Regions {
+        &mut self.regions
+    }
+
+    /// Drops every chunk whose portals, paths or regions could lead through the block.
+    /// They are rebuilt once a search reaches them again.
+    pub(crate) fn invalidate(&mut self, coordinates: IWorldCoordinates) {
+        self.generation += 1;
+        // the block decides whether the block above it can be stood on
+        let mut affected = vec![];
+        for block in [coordinates, IWorldCoordinates(coordinates.0 + IVec3::Z)] {
+            // chunks next to the block reach it from their border
+            for chunk in std::iter::once(block.chunk())
+                .chain(block.neighboring_chunks(Connectivity::Corners))
+            {
+                if !affected.contains(&chunk) {
+                    affected.push(chunk);
+                }
+            }
+        }
+        for chunk in affected {
+            if self.chunks.contains_key(&chunk.0) {
+                Arc::make_mut(&mut self.chunks).remove(&chunk.0);
+            }
+            self.regions.invalidate(chunk.0);
+            Arc::make_mut(&mut self.invalidated).insert(chunk.0, self.generation);
+        }
+    }
+
+    /// Adds the chunks a clone has built, unless they were invalidated after the clone was made.
+    pub(crate) fn merge(&mut self, clone: ChunkGraph) {
+        let stale = |chunk: IVec3| {
+            self.invalidated
+                .get(&chunk)
+                .is_some_and(|generation| *generation > clone.generation)
+        };
+        self.regions.merge(clone.regions, stale);
+        if Arc::ptr_eq(&self.chunks, &clone.chunks) {
+            return;
+        }
+        for (chunk, nodes) in clone.chunks.iter() {
+            if !stale(*chunk) && !self.chunks.contains_key(chunk) {
+                Arc::make_mut(&mut self.chunks).insert(*chunk, nodes.clone());
+            }
+        }
+    }
+
+    /// Returns true if the portals of the chunk are known.
+    pub(crate) fn contains(&self, chunk: ChunkCoordinates) -> bool {
+        self.chunks.contains_key(&chunk.0)
+    }
+
+    /// Returns the current generation, to start a [`ChunkBuilder`] with.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Adds a chunk that is done building, unless it was invalidated since building started.
+    pub(crate) fn insert(&mut self, builder: ChunkBuilder) {
+        let stale = self
+            .invalidated
+            .get(&builder.chunk.0)
+            .is_some_and(|generation| *generation > builder.generation);
+        if !stale {
+            Arc::make_mut(&mut self.chunks).insert(builder.chunk.0, Arc::new(builder.nodes));
+        }
+    }
+
+    fn nodes(&mut self, world_map: &mut WorldMap, chunk: ChunkCoordinates) -> &ChunkNodes {
+        if !self.chunks.contains_key(&chunk.0) {
+            let nodes = Arc::new(ChunkNodes::build(world_map, chunk));
+            Arc::make_mut(&mut self.chunks).insert(chunk.0, nodes);
+        }
+        &self.chunks[&chunk.0]
+    }
+}
+
+impl ChunkNodes {
+    fn build(world_map: &mut WorldMap, chunk: ChunkCoordinates) -> Self {
+        // the blocks around the chunk decide where it can be entered and left
+        world_map.ensure_surrounding_exist(chunk);
+        let mut builder = ChunkBuilder::new(world_map, chunk, 0);
+        while !builder.step(world_map) {}
+        builder.nodes
+    }
+}
+
+/// A chunk that is added to the graph one portal at a time, so the work can be spread over
+/// several steps of a search.
+pub(crate) struct ChunkBuilder {
+    chunk: ChunkCoordinates,
+    nodes: ChunkNodes,
+    portals: Vec<IVec3>,
+    /// How many of the portals are connected to the others.
+    connected: usize,
+    /// The generation of the graph when building started, see [`ChunkGraph::insert`].
+    generation: u64,
+}
+
+impl ChunkBuilder {
+    /// Finds the portals of the chunk. The chunks around it have to be in memory.
+    pub(crate) fn new(world_map: &WorldMap, chunk: ChunkCoordinates, generation: u64) -> Self {
+        let mut edges: HashMap<IVec3, Vec<Edge>> = HashMap::default();
+        for (neighbor, _) in chunk.all_neighbors() {
+            for (portal, other) in entrances(world_map, chunk, neighbor) {
+                let Some(cost) = world_map.move_cost(portal, other) else {
+                    continue;
+                };
+                edges.entry(portal).or_default().push(Edge {
+                    to: other,
+                    cost,
+                    path: vec![other],
+                });
+            }
+        }
+        ChunkBuilder {
+            chunk,
+            portals: edges.keys().copied().collect(),
+            nodes: ChunkNodes { edges },
+            connected: 0,
+            generation,
+        }
+    }
+
+    pub(crate) fn chunk(&self) -> ChunkCoordinates {
+        self.chunk
+    }
+
+    /// Connects the next portal to the others. Returns true once all of them are connected.
+    pub(crate) fn step(&mut self, world_map: &WorldMap) -> bool {
+        if let Some(portal) = self.portals.get(self.connected) {
+            let paths = search_within(world_map, self.chunk, &[*portal], &self.portals, false);
+            self.nodes
+                .edges
+                .entry(*portal)
+                .or_default()
+                .extend(paths.into_iter().map(|(_, edge)| edge));
+            self.connected += 1;
+        }
+        self.connected >= self.portals.len()
+    }
+}
+
+/// Returns one pair of blocks for every entrance from `chunk` into `other`, the first in `chunk`
+/// and the second in `other`.
+fn entrances(
+    world_map: &WorldMap,
+    chunk: ChunkCoordinates,
+    other: ChunkCoordinates,
+) -> Vec<(IVec3, IVec3)> {
+    // always looked for from the same side, so both chunks agree on their entrances
+    if chunk.0.to_array() > other.0.to_array() {
+        return entrances(world_map, other, chunk)
+            .into_iter()
+            .map(|(portal, other)| (other, portal))
+            .collect();
+    }
+    let offset = other.0 - chunk.0;
+    let range = |axis: usize| match offset[axis] {
+        1 => CHUNK_SIZE[axis] - 1..CHUNK_SIZE[axis],
+        -1 => 0..1,
+        _ => 0..CHUNK_SIZE[axis],
+    };
+    let mut transitions = vec![];
+    for x in range(0) {
+        for y in range(1) {
+            for z in range(2) {
+                let block = chunk.block((x, y, z));
+                if world_map.footing(block.0).is_none() {
+                    continue;
+                }
+                for (neighbor, _) in block.all_neighbors() {
+                    if neighbor.chunk() == other
+                        && world_map.footing(neighbor.0).is_some()
+                        && world_map.can_move(block.0, neighbor.0)
+                    {
+                        transitions.push((block.0, neighbor.0));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut entrances = vec![];
+    let mut grouped = vec![false; transitions.len()];
+    for first in 0..transitions.len() {
+        if grouped[first] {
+            continue;
+        }
+        grouped[first] = true;
+        let mut group = vec![first];
+        let mut next = 0;
+        while next < group.len() {
+            let (block, _) = transitions[group[next]];
+            for (index, (other_block, _)) in transitions.iter().enumerate() {
+                // only blocks that can be walked between along the border form one entrance
+                let connected = block == *other_block
+                    || IWorldCoordinates(block).adjacency(&IWorldCoordinates(*other_block))
+                        == Some(Adjacency::Face)
+                        && world_map.can_move(block, *other_block);
+                if !grouped[index] && connected {
+                    grouped[index] = true;
+                    group.push(index);
+                }
+            }
+            next += 1;
+        }
+        let mut blocks: Vec<IVec3> = group.iter().map(|index| transitions[*index].0).collect();
+        blocks.sort_by_key(|block| block.to_array());
+        blocks.dedup();
+        let middle = blocks.iter().map(|block| block.as_vec3()).sum::<Vec3>() / blocks.len() as f32;
+        let farthest = |from: Vec3| {
+            let distance = |index: &usize| transitions[*index].0.as_vec3().distance_squared(from);
+            *group
+                .iter()
+                .max_by(|a, b| distance(a).total_cmp(&distance(b)))
+                .unwrap()
+        };
+        if blocks.len() >= LONG_ENTRANCE {
+            let end = farthest(middle);
+            let other_end = farthest(transitions[end].0.as_vec3());
+            entrances.extend([transitions[end], transitions[other_end]]);
+        } else {
+            let distance = |index: &usize| transitions[*index].0.as_vec3().distance_squared(middle);
+            let closest = group
+                .iter()
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                .unwrap();
+            entrances.push(transitions[*closest]);
+        }
+    }
+    entrances
+}
+
+/// Searches from all `starts` at once without leaving `chunk`, until every goal is reached.
+/// Returns the edges to the goals it reached from whichever start is the closest to them, together
+/// with the block they start at.
+///
+/// If `reverse` is set, the search follows the moves backwards and the edges lead from the goals
+/// to the closest start instead.
+pub(crate) fn search_within(
+    world_map: &WorldMap,
+    chunk: ChunkCoordinates,
+    starts: &[IVec3],
+    goals: &[IVec3],
+    reverse: bool,
+) -> Vec<(IVec3, Edge)> {
+    let bounds = world_map.bounds();
+    let neighbors = |current: IVec3| -> Vec<(IVec3, f32)> {
+        if !reverse {
+            return world_map.get_neighbors(current).collect();
+        }
+        let Some(footing) = world_map.footing(current) else {
+            return vec![];
+        };
+        current
+            .all_neighbors()
+            .into_iter()
+            .filter(|(neighbor, _)| {
+                bounds.contains(*neighbor)
+                    && world_map.footing(*neighbor).is_some()
+                    && world_map.can_move(*neighbor, current)
+            })
+            .map(|(neighbor, _)| (neighbor, footing * distance(neighbor, current)))
+            .collect()
+    };
+
+    let mut frontier = PriorityQueue::new();
+    let mut came_from: HashMap<IVec3, IVec3> = HashMap::default();
+    let mut cost_so_far: HashMap<IVec3, f32> = HashMap::default();
+    for start in starts {
+        frontier.push(*start, Reverse(Cost(0.0)));
+        cost_so_far.insert(*start, 0.0);
+    }
+    let mut remaining = goals.iter().filter(|goal| !starts.contains(goal)).count();
+    let mut edges = vec![];
+    while remaining > 0
+        && let Some((current, Reverse(Cost(cost)))) = frontier.pop()
+    {
+        if !starts.contains(&current) && goals.contains(&current) {
+            let mut path = vec![];
+            let mut next = current;
+            while let Some(previous) = came_from.get(&next) {
+                path.push(if reverse { *previous } else { next });
+                next = *previous;
+            }
+            // the search ran back to the start it came from
+            let start = next;
+            let (from, to) = if reverse {
+                (current, start)
+            } else {
+                path.reverse();
+                (start, current)
+            };
+            edges.push((from, Edge { to, cost, path }));
+            remaining -= 1;
+        }
+        for (neighbor, step) in neighbors(current) {
+            if IWorldCoordinates(neighbor).chunk() != chunk {
+                continue;
+            }
+            let new_cost = cost + step;
+            if cost_so_far
+                .get(&neighbor)
+                .is_none_or(|known| new_cost < *known)
+            {
+                cost_so_far.insert(neighbor, new_cost);
+                came_from.insert(neighbor, current);
+                frontier.push(neighbor, Reverse(Cost(new_cost)));
+            }
+        }
+    }
+    edges
+}
+
+/// Drops the parts of the graph that changed blocks lead through and labels the dropped regions
+/// again, with up to half of the [`PathfindingBudget`]. That way, other crates can tell what is
+/// reachable again soon after the map changed.
+pub(crate) fn invalidate(
+    mut block_updates: MessageReader<BlockUpdate>,
+    mut graph: ResMut<ChunkGraph>,
+    world_map: Res<WorldMap>,
+    budget: Res<PathfindingBudget>,
+    mut stats: ResMut<PathfindingStats>,
+) {
+    for block_update in block_updates.read() {
+        match block_update {
+            BlockUpdate::Added(coordinates) | BlockUpdate::Removed(coordinates) => {
+                graph.invalidate(*coordinates);
+            }
+            BlockUpdate::ScheduleForRemoval(_) => (),
+        }
+    }
+    let limit = (budget.expansions_per_frame / 2 / CHUNK_COST).max(1);
+    stats.relabelled = graph.regions.relabel(&world_map, limit as usize);
+}
+
+#[test]
+fn test_entrances() {
+    use map_generation::testing;
+
+    // a border open along its whole length, and one with a gap of two blocks
+    let mut world_map = testing::floor(0..32, 0..16);
+    let chunk = ChunkCoordinates(IVec3::new(0, 0, 31));
+    let other = ChunkCoordinates(IVec3::new(1, 0, 31));
+    assert_eq!(IWorldCoordinates(IVec3::new(2, 2, 501)).chunk(), chunk);
+    let mut ends: Vec<i32> = entrances(&world_map, chunk, other)
+        .into_iter()
+        .map(|(portal, _)| portal.y)
+        .collect();
+    ends.sort();
+    assert_eq!(ends, [0, 15]);
+
+    testing::wall(
+        &mut world_map,
+        (0..16)
+            .filter(|y| !(7..9).contains(y))
+            .map(|y| IVec2::new(16, y)),
+    );
+    let portals = entrances(&world_map, chunk, other);
+    assert_eq!(portals.len(), 1);
+    assert!((7..9).contains(&portals[0].0.y));
+}
+
+#[test]
+fn test_invalidate() {
+    use map_generation::testing;
+
+    let mut world_map = testing::floor(0..48, 0..16);
+    let chunks = (0..3).map(|x| ChunkCoordinates(IVec3::new(x, 0, 31)));
+    let mut graph = ChunkGraph::default();
+    for chunk in chunks.clone() {
+        graph.portals(&mut world_map, chunk);
+    }
+    let contained = |graph: &ChunkGraph| {
+        chunks
+            .clone()
+            .map(|chunk| graph.contains(chunk))
+            .collect::<Vec<_>>()
+    };
+
+    // a block in the middle of a chunk only drops that chunk
+    graph.invalidate(IWorldCoordinates(IVec3::new(40, 8, 501)));
+    assert_eq!(contained(&graph), [true, true, false]);
+
+    // a block on a border drops the chunks on both sides
+    graph.invalidate(IWorldCoordinates(IVec3::new(15, 8, 501)));
+    assert_eq!(contained(&graph), [false, false, false]);
+
+    // blocks below only drop a chunk if they hold up a block next to it
+    for chunk in chunks.clone() {
+        graph.portals(&mut world_map, chunk);
+    }
+    graph.invalidate(IWorldCoordinates(IVec3::new(8, 8, 493)));
+    assert_eq!(contained(&graph), [true, true, true]);
+    graph.invalidate(IWorldCoordinates(IVec3::new(8, 8, 495)));
+    assert_eq!(contained(&graph), [false, true, true]);
+}
@@ -1,11 +1,16 @@
 use bevy::prelude::*;
 use path::Path;
 
+pub mod budget;
+mod hierarchy;
 pub mod path;
 pub mod pathfinder;
 mod pathfinding;
 pub mod pathfinding_map;
+mod regions;
+pub use hierarchy::ChunkGraph;
 pub use pathfinding::plugin;
+pub use regions::{Reach, Regions};
 
 #[derive(EntityEvent)]
 pub struct PathEvent {
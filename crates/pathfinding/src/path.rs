@@ -1,8 +1,9 @@
 use bevy::prelude::*;
 use std::time::Duration;
 
-use crate::{PathEvent, PathState};
-use common::types::{IWorldCoordinates, WorldCoordinates};
+use crate::{PathEvent, PathState, hierarchy::search_within, pathfinder::Pathfinder};
+use common::coordinates::{IWorldCoordinates, WorldCoordinates};
+use map_generation::{messages::BlockUpdate, residency::ChunkPins, world_map::WorldMap};
 
 #[derive(Clone, Component, Debug, PartialEq, Reflect)]
 #[reflect(Component)]
@@ -21,6 +22,11 @@ impl Path {
         }
     }
 
+    /// Returns the block the path ends at.
+    pub fn destination(&self) -> IWorldCoordinates {
+        *self.set.last().unwrap()
+    }
+
     fn tick(&mut self, delta: Duration) {
         self.current_t += delta.as_secs_f32();
         if self.current_t > 1.0 {
@@ -33,6 +39,55 @@ impl Path {
         self.current_index >= self.set.len()
     }
 
+    /// Returns the index of the first point from the current one on that can't be reached from the
+    /// one before anymore. Only steps next to `changed` blocks are checked.
+    fn first_blocked(&self, world_map: &WorldMap, changed: &[IWorldCoordinates]) -> Option<usize> {
+        let near = |point: &IWorldCoordinates| {
+            changed
+                .iter()
+                .any(|block| (block.0 - point.0).abs().max_element() <= 1)
+        };
+        (self.current_index + 1..self.set.len()).find(|index| {
+            let (from, to) = (self.set[index - 1], self.set[*index]);
+            (near(&from) || near(&to))
+                && (!world_map.can_move(from.0, to.0) || world_map.footing(to.0).is_none())
+        })
+    }
+
+    /// Tries to lead around a blocked point, without leaving the chunk of the point before it.
+    /// Rejoins the path as far along as possible. Returns false if there is no way around.
+    fn repair(&mut self, world_map: &WorldMap, blocked: usize) -> bool {
+        let start = self.set[blocked - 1];
+        if world_map.footing(start.0).is_none() {
+            return false;
+        }
+        let chunk = start.chunk();
+        let goals: Vec<IVec3> = self.set[blocked..]
+            .iter()
+            .filter(|point| point.chunk() == chunk)
+            .map(|point| point.0)
+            .collect();
+        let Some(detour) = search_within(world_map, chunk, &[start.0], &goals, false)
+            .into_iter()
+            .map(|(_, edge)| edge)
+            .filter_map(|edge| {
+                let rejoin = self.set[blocked..]
+                    .iter()
+                    .rposition(|point| point.0 == edge.to)?;
+                Some((blocked + rejoin, edge))
+            })
+            .max_by_key(|(rejoin, _)| *rejoin)
+        else {
+            return false;
+        };
+        let (rejoin, edge) = detour;
+        self.set.splice(
+            blocked..=rejoin,
+            edge.path.into_iter().map(IWorldCoordinates),
+        );
+        true
+    }
+
     fn current_position(&self) -> WorldCoordinates {
         if self.current_index + 1 >= self.set.len() {
             return self.set.last().unwrap().into();
@@ -43,9 +98,14 @@ impl Path {
     }
 }
 
+/// Marks an entity whose [`Path`] was blocked and is being searched again. The path isn't followed
+/// until the new one is found.
+#[derive(Component)]
+pub(crate) struct Replanning;
+
 pub(crate) fn tick_path(
     time: Res<Time>,
-    mut query: Query<(Entity, &mut Path)>,
+    mut query: Query<(Entity, &mut Path), Without<Replanning>>,
     mut commands: Commands,
 ) {
     for (entity, mut path) in &mut query {
@@ -61,8 +121,89 @@ pub(crate) fn tick_path(
     }
 }
 
-pub(crate) fn follow_path(mut query: Query<(&mut WorldCoordinates, &Path)>) {
+pub(crate) fn follow_path(mut query: Query<(&mut WorldCoordinates, &Path), Without<Replanning>>) {
     for (mut transform, path) in &mut query {
         *transform = path.current_position();
     }
 }
+
+/// Checks the paths against changed blocks. Blocked paths are led around the obstacle if there is a
+/// way within the chunk, otherwise a new path to their destination is searched.
+///
+/// If there is no path anymore, the entity gets a [`PathState::CalculationFailed`].
+pub(crate) fn check_paths(
+    mut block_updates: MessageReader<BlockUpdate>,
+    world_map: Res<WorldMap>,
+    mut query: Query<(Entity, &mut Path), Without<Replanning>>,
+    mut commands: Commands,
+) {
+    let changed: Vec<IWorldCoordinates> = block_updates
+        .read()
+        .filter_map(|block_update| match block_update {
+            BlockUpdate::Added(coordinates) | BlockUpdate::Removed(coordinates) => {
+                Some(*coordinates)
+            }
+            BlockUpdate::ScheduleForRemoval(_) => None,
+        })
+        .collect();
+    if changed.is_empty() {
+        return;
+    }
+    for (entity, mut path) in &mut query {
+        let Some(blocked) = path.first_blocked(&world_map, &changed) else {
+            continue;
+        };
+        if path.repair(&world_map, blocked) && path.first_blocked(&world_map, &changed).is_none() {
+            debug!("led the path of {} around {:?}", entity, path.set[blocked]);
+            // pins the chunks of the detour
+            commands.entity(entity).insert(path.clone());
+            continue;
+        }
+        debug!("path of {} is blocked, searching a new one", entity);
+        let from = path.set[path.current_index.min(path.set.len() - 1)];
+        let destination = path.destination();
+        commands
+            .entity(entity)
+            .insert((Replanning, Pathfinder::exact(from, destination)));
+    }
+}
+
+/// Keeps every chunk along the path in memory, so it can be followed.
+pub(crate) fn pin_path(trigger: On<Insert, Path>, query: Query<&Path>, mut commands: Commands) {
+    let path = query.get(trigger.entity).unwrap();
+    commands
+        .entity(trigger.entity)
+        .insert(ChunkPins::containing(path.set.iter().copied()));
+}
+
+pub(crate) fn unpin_path(trigger: On<Remove, Path>, mut commands: Commands) {
+    commands.entity(trigger.entity).try_remove::<ChunkPins>();
+}
+
+#[test]
+fn test_repair_path() {
+    use map_generation::testing;
+
+    let mut world_map = testing::floor(0..16, 0..16);
+    let mut path = Path::new(
+        (2..11)
+            .map(|x| IWorldCoordinates(IVec3::new(x, 5, 501)))
+            .collect(),
+    );
+    let changed = [IWorldCoordinates(IVec3::new(6, 5, 501))];
+    assert_eq!(path.first_blocked(&world_map, &changed), None);
+
+    testing::wall(&mut world_map, [changed[0].0.truncate()]);
+    let blocked = path.first_blocked(&world_map, &changed).unwrap();
+    assert_eq!(path.set[blocked], changed[0]);
+    assert!(path.repair(&world_map, blocked));
+    assert_eq!(path.first_blocked(&world_map, &changed), None);
+    assert!(!path.set.contains(&changed[0]));
+    assert_eq!(
+        path.set.last(),
+        Some(&IWorldCoordinates(IVec3::new(10, 5, 501)))
+    );
+    for step in path.set.windows(2) {
+        assert!(world_map.can_move(step[0].0, step[1].0));
+    }
+}
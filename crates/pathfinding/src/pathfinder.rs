@@ -1,23 +1,74 @@
 use std::cmp::Reverse;
 
-use bevy::{ecs::spawn::SpawnIter, platform::collections::HashMap, prelude::*};
-use common::{traits::Neighbors, types::IWorldCoordinates};
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use common::{
+    coordinates::{ChunkCoordinates, IWorldCoordinates},
+    traits::Neighbors,
+};
+use map_generation::{
+    movement::{MIN_TRAVERSAL_COST, distance},
+    residency::ChunkPins,
+    world_map::WorldMap,
+};
 use priority_queue::PriorityQueue;
 
-use crate::{path::Path, pathfinding_map::PathfindingMap};
+use crate::{
+    hierarchy::{ChunkBuilder, ChunkGraph, Cost, Edge, search_within},
+    path::Path,
+    pathfinding_map::PathfindingMap,
+    regions::Exploration,
+};
 
 /// Attach this to calculate and ultimately follow a path.
 ///
+/// The search runs on the [`ChunkGraph`]: from the start to the portals of its chunk, between
+/// portals and from the portals of the goals' chunks to the goals. It ends at whichever goal is the
+/// cheapest to reach.
+///
+/// The portals rarely lie on the cheapest path, so the path they lead along is only a first guess.
+/// Once it is found, the search starts over on the blocks of the chunks along it and around them,
+/// which finds the cheapest path within those chunks.
+///
+/// Chunks the search needs are prepared along the way, a single chunk generated or labelled, or a
+/// single portal connected, per step. That way, no step takes much longer than the others.
+///
 /// Internally, Pathfinder uses IVec3 but these represent WorldCoordinates.
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 pub struct Pathfinder {
-    target: IVec3,
+    start: IVec3,
+    goals: Vec<IVec3>,
     #[reflect(ignore)]
-    frontier: PriorityQueue<IVec3, Reverse<u32>>,
+    frontier: PriorityQueue<IVec3, Reverse<Cost>>,
     came_from: HashMap<IVec3, Option<IVec3>>,
+    /// The blocks leading to a node from the one it came from.
+    segments: HashMap<IVec3, Vec<IVec3>>,
     cost_so_far: HashMap<IVec3, f32>,
+    /// Edges from the start to the portals of its chunk, and to the goals in the same chunk.
+    #[reflect(ignore)]
+    start_edges: Vec<Edge>,
+    /// Edges from the portals of the goals' chunks to the goals.
+    #[reflect(ignore)]
+    goal_edges: HashMap<IVec3, Vec<Edge>>,
+    /// Whether the regions were checked for a way to the goals.
+    explored: bool,
+    /// The chunks whose start or goals still have to be connected to their portals.
+    unconnected: Vec<ChunkCoordinates>,
+    /// The chunk that is being prepared.
+    preparing: Option<ChunkCoordinates>,
+    /// The chunk that is being added to the graph.
+    #[reflect(ignore)]
+    building: Option<ChunkBuilder>,
+    /// The chunks the refining search stays within. None while the search runs over the portals.
+    corridor: Option<HashSet<IVec3>>,
+    /// How many steps went into preparing chunks.
+    pub(crate) prepared: u32,
     steps: u32,
+    /// How far the last batch went over its share of the budget.
+    pub(crate) overspent: u32,
     allowed_failures: u8,
     current_failures: u8,
 }
@@ -25,11 +76,22 @@ pub struct Pathfinder {
 impl Default for Pathfinder {
     fn default() -> Self {
         Pathfinder {
-            target: IVec3::ZERO,
+            start: IVec3::ZERO,
+            goals: vec![],
             frontier: PriorityQueue::new(),
             came_from: HashMap::new(),
+            segments: HashMap::new(),
             cost_so_far: HashMap::new(),
+            start_edges: vec![],
+            goal_edges: HashMap::new(),
+            explored: false,
+            unconnected: vec![],
+            preparing: None,
+            building: None,
+            corridor: None,
+            prepared: 0,
             steps: 0,
+            overspent: 0,
             allowed_failures: 3,
             current_failures: 0,
         }
@@ -43,18 +105,27 @@ impl Default for Pathfinder {
 pub(crate) struct PathfinderListener;
 
 impl Pathfinder {
-    /// Creates a new pathfinder that will try to find a path via A* from start to target
-    fn new(start: IWorldCoordinates, target: IWorldCoordinates) -> Self {
-        let frontier = PriorityQueue::from(vec![(start.0, Reverse(0))]);
+    /// Creates a new pathfinder that will try to find a path via A* from start to the nearest goal
+    fn new(start: IWorldCoordinates, goals: Vec<IWorldCoordinates>) -> Self {
+        let frontier = PriorityQueue::from(vec![(start.0, Reverse(Cost(0.0)))]);
         let mut came_from = HashMap::default();
         came_from.insert(start.0, None);
         let mut cost_so_far = HashMap::default();
         cost_so_far.insert(start.0, 0.0);
+        // the start's chunk is connected first
+        let mut unconnected: Vec<ChunkCoordinates> =
+            goals.iter().map(|goal| goal.chunk()).collect();
+        unconnected.retain(|chunk| *chunk != start.chunk());
+        unconnected.sort_by_key(|chunk| chunk.0.to_array());
+        unconnected.dedup();
+        unconnected.push(start.chunk());
         Pathfinder {
-            target: target.0,
+            start: start.0,
+            goals: goals.into_iter().map(|goal| goal.0).collect(),
             frontier,
             came_from,
             cost_so_far,
+            unconnected,
             ..default()
         }
     }
@@ -63,50 +134,115 @@ impl Pathfinder {
     ///
     /// Use this, if an entity has to land exactly on the given target
     pub fn exact(start: IWorldCoordinates, target: IWorldCoordinates) -> impl Bundle {
-        (
-            PathfinderListener,
-            children![(
-                Pathfinder::new(start, target),
-                Name::new(format!("Pathfinder for {:?}", target.0))
-            )],
-        )
+        Pathfinder::any(start, [target])
     }
 
-    /// Spawns a PathfinderListener with separate Pathfinder children targeting the blocks surrounding the given target
+    /// Spawns a PathfinderListener with one Pathfinder child targeting the blocks surrounding the given target
     ///
     /// Use this if an entity has to come close to a given target but not go onto it
     pub fn nearest(start: IWorldCoordinates, target: IWorldCoordinates) -> impl Bundle {
-        let finders: Vec<(Pathfinder, Name)> = target
-            .same_layer_neighbors()
-            .iter()
-            .map(|(coordinates, _)| {
-                (
-                    Pathfinder::new(start, *coordinates),
-                    Name::new(format!("Pathfinder for {:?}", coordinates.0)),
-                )
-            })
-            .collect();
+        Pathfinder::any(
+            start,
+            target
+                .same_layer_neighbors()
+                .into_iter()
+                .map(|(coordinates, _)| coordinates),
+        )
+    }
+
+    /// Spawns a PathfinderListener with one Pathfinder child targeting whichever goal is the cheapest to reach
+    ///
+    /// Use this to go to the nearest of several things, like the nearest item of a kind or the nearest free bed,
+    /// by passing the blocks they are at. The path ends at the goal that was reached.
+    pub fn any(
+        start: IWorldCoordinates,
+        goals: impl IntoIterator<Item = IWorldCoordinates>,
+    ) -> impl Bundle {
+        let goals: Vec<IWorldCoordinates> = goals.into_iter().collect();
+        let name = match goals.as_slice() {
+            [goal] => format!("Pathfinder for {:?}", goal.0),
+            _ => format!("Pathfinder for {} goals", goals.len()),
+        };
         (
             PathfinderListener,
-            Children::spawn(SpawnIter(finders.into_iter())),
+            children![(
+                ChunkPins::surrounding(std::iter::once(start).chain(goals.iter().copied())),
+                Pathfinder::new(start, goals),
+                Name::new(name)
+            )],
         )
     }
 
     pub(crate) fn calculate_step(
         &mut self,
-        pathfinding_map: &impl PathfindingMap,
+        world_map: &mut WorldMap,
+        graph: &mut ChunkGraph,
     ) -> PathfindingState {
-        let Some((current_coordinates, _current_priority)) = self.frontier.pop() else {
+        if self.goals.is_empty() {
+            debug!("no goals to reach");
+            return PathfindingState::Failed(PathfindingErrors::Unreachable);
+        }
+        if !self.explored {
+            let goals = self.goals.iter().copied().map(IWorldCoordinates);
+            match graph
+                .regions_mut()
+                .explore_step(world_map, IWorldCoordinates(self.start), goals)
+            {
+                Exploration::Pending(chunk) => {
+                    self.preparing = Some(chunk);
+                    self.prepared += 1;
+                    return PathfindingState::Calculating;
+                }
+                Exploration::Done(Some(false)) => {
+                    debug!("no goal is in a region connected to the start");
+                    return PathfindingState::Failed(PathfindingErrors::Unreachable);
+                }
+                Exploration::Done(_) => {
+                    self.explored = true;
+                    self.preparing = None;
+                }
+            }
+        }
+        if let Some(chunk) = self.unconnected.last().copied() {
+            // the graph might know the chunk already, but its blocks are needed as well
+            if !self.prepare(world_map, graph, chunk) && !world_map.generate_surrounding(chunk) {
+                self.connect(world_map, graph, chunk);
+                self.unconnected.pop();
+            }
+            self.prepared += 1;
+            return PathfindingState::Calculating;
+        }
+        let Some((next, _)) = self.frontier.peek() else {
             debug!("No frontier available");
             return PathfindingState::Failed(PathfindingErrors::Unreachable);
         };
+        let next_chunk = IWorldCoordinates(*next).chunk();
+        let prepared = match self.corridor {
+            // the refining search only needs the blocks
+            Some(_) => world_map.generate_surrounding(next_chunk),
+            None => self.prepare(world_map, graph, next_chunk),
+        };
+        if prepared {
+            self.prepared += 1;
+            return PathfindingState::Calculating;
+        }
+        let (current_coordinates, _current_priority) = self.frontier.pop().unwrap();
 
-        if current_coordinates == self.target {
-            debug!("frontier is target");
-            return PathfindingState::Complete(Path::new(self.to_path()));
+        if self.goals.contains(&current_coordinates) {
+            if self.corridor.is_some() {
+                debug!("frontier is a goal");
+                return PathfindingState::Complete(Path::new(self.to_path(current_coordinates)));
+            }
+            self.refine(current_coordinates);
+            return PathfindingState::Calculating;
         }
 
-        for (neighbor, neighbor_cost) in pathfinding_map.get_neighbors(current_coordinates) {
+        for Edge {
+            to: neighbor,
+            cost: neighbor_cost,
+            path,
+        } in self.edges(world_map, graph, current_coordinates)
+        {
             debug!(
                 "current {:?} to neighbor {:?} would cost {}",
                 current_coordinates, neighbor, neighbor_cost
@@ -115,37 +251,160 @@ impl Pathfinder {
             let current_cost = self.cost_so_far.get(&neighbor);
             if current_cost.is_none() || new_cost < *current_cost.unwrap() {
                 self.cost_so_far.insert(neighbor, new_cost);
-                let priority = new_cost + heuristic(neighbor, self.target);
-                self.frontier
-                    .push(neighbor, Reverse(priority.round() as u32));
+                let priority = new_cost + self.heuristic(neighbor);
+                self.frontier.push(neighbor, Reverse(Cost(priority)));
                 self.came_from.insert(neighbor, Some(current_coordinates));
+                self.segments.insert(neighbor, path);
             }
         }
         self.steps += 1;
         PathfindingState::Calculating
     }
 
-    fn to_path(&self) -> Vec<IWorldCoordinates> {
+    /// Does a single step of preparing a chunk for the search: generates one of the chunks around
+    /// it or connects one of its portals. Returns false if the chunk is part of the graph already.
+    fn prepare(
+        &mut self,
+        world_map: &mut WorldMap,
+        graph: &mut ChunkGraph,
+        chunk: ChunkCoordinates,
+    ) -> bool {
+        if graph.contains(chunk) {
+            return false;
+        }
+        self.preparing = Some(chunk);
+        // the chunks around it decide where it can be entered and might have been dropped since
+        if world_map.generate_surrounding(chunk) {
+            return true;
+        }
+        let Some(builder) = self
+            .building
+            .as_mut()
+            .filter(|builder| builder.chunk() == chunk)
+        else {
+            self.building = Some(ChunkBuilder::new(world_map, chunk, graph.generation()));
+            return true;
+        };
+        if builder.step(world_map) {
+            // a chunk dropped while it was built is started over
+            graph.insert(self.building.take().unwrap());
+            self.preparing = None;
+        }
+        true
+    }
+
+    /// Connects the start or the goals in a chunk to the portals of the chunk. The chunk has to be
+    /// prepared.
+    fn connect(
+        &mut self,
+        world_map: &mut WorldMap,
+        graph: &mut ChunkGraph,
+        chunk: ChunkCoordinates,
+    ) {
+        let portals = graph.portals(world_map, chunk);
+        let goals: Vec<IVec3> = self
+            .goals
+            .iter()
+            .copied()
+            .filter(|goal| IWorldCoordinates(*goal).chunk() == chunk)
+            .collect();
+        if IWorldCoordinates(self.start).chunk() == chunk {
+            let targets: Vec<IVec3> = portals.iter().chain(&goals).copied().collect();
+            self.start_edges = search_within(world_map, chunk, &[self.start], &targets, false)
+                .into_iter()
+                .map(|(_, edge)| edge)
+                .collect();
+        }
+        // searching from every goal of a chunk at once leads each portal to the closest of them
+        for (portal, edge) in search_within(world_map, chunk, &goals, &portals, true) {
+            self.goal_edges.entry(portal).or_default().push(edge);
+        }
+    }
+
+    /// Starts the search over on the blocks of the chunks the path through the portals leads
+    /// through, and the chunks around them.
+    fn refine(&mut self, goal: IVec3) {
+        let mut corridor = HashSet::new();
+        for point in self.to_path(goal) {
+            let chunk = point.chunk();
+            corridor.insert(chunk.0);
+            corridor.extend(
+                chunk
+                    .all_neighbors()
+                    .into_iter()
+                    .map(|(neighbor, _)| neighbor.0),
+            );
+        }
+        self.corridor = Some(corridor);
+        self.frontier = PriorityQueue::from(vec![(self.start, Reverse(Cost(0.0)))]);
+        self.came_from = HashMap::from_iter([(self.start, None)]);
+        self.cost_so_far = HashMap::from_iter([(self.start, 0.0)]);
+        self.segments.clear();
+    }
+
+    /// Returns the edges leaving a node, including those to the goals.
+    fn edges(
+        &self,
+        world_map: &mut WorldMap,
+        graph: &mut ChunkGraph,
+        coordinates: IVec3,
+    ) -> Vec<Edge> {
+        if let Some(corridor) = &self.corridor {
+            return world_map
+                .get_neighbors(coordinates)
+                .filter(|(neighbor, _)| corridor.contains(&IWorldCoordinates(*neighbor).chunk().0))
+                .map(|(neighbor, cost)| Edge {
+                    to: neighbor,
+                    cost,
+                    path: vec![neighbor],
+                })
+                .collect();
+        }
+        let mut edges = graph.edges(world_map, coordinates).to_vec();
+        if coordinates == self.start {
+            edges.extend(self.start_edges.iter().cloned());
+        }
+        if let Some(goal_edges) = self.goal_edges.get(&coordinates) {
+            edges.extend(goal_edges.iter().cloned());
+        }
+        edges
+    }
+
+    /// Returns the start, the goals, every node the search has reached so far and the chunk it
+    /// prepares. The chunks around them are the ones the search still needs.
+    pub(crate) fn reached(&self) -> impl Iterator<Item = IWorldCoordinates> {
+        std::iter::once(&self.start)
+            .chain(&self.goals)
+            .chain(self.came_from.keys())
+            .map(|coordinates| IWorldCoordinates(*coordinates))
+            .chain(self.preparing.map(|chunk| chunk.origin()))
+    }
+
+    /// Never overestimates the cost of the remaining path to the nearest goal, so the path found is
+    /// the cheapest.
+    fn heuristic(&self, from: IVec3) -> f32 {
+        self.goals
+            .iter()
+            .map(|goal| MIN_TRAVERSAL_COST * distance(from, *goal))
+            .min_by(f32::total_cmp)
+            .unwrap_or_default()
+    }
+
+    fn to_path(&self, goal: IVec3) -> Vec<IWorldCoordinates> {
         let mut points = vec![];
-        let mut next = self.target;
-        points.push(IWorldCoordinates(next));
-        while let Some(point_option) = self.came_from.get(&next) {
-            if let Some(point) = point_option {
-                points.push(IWorldCoordinates(*point));
-                next = *point;
-            } else {
-                break;
+        let mut next = goal;
+        while let Some(Some(previous)) = self.came_from.get(&next) {
+            if let Some(segment) = self.segments.get(&next) {
+                points.extend(segment.iter().rev().map(|point| IWorldCoordinates(*point)));
             }
+            next = *previous;
         }
+        points.push(IWorldCoordinates(self.start));
         points.reverse();
         points
     }
 }
 
-fn heuristic(from: IVec3, to: IVec3) -> f32 {
-    from.distance_squared(to) as f32
-}
-
 pub(crate) enum PathfindingState {
     Failed(PathfindingErrors),
     Calculating,
@@ -155,3 +414,224 @@ pub(crate) enum PathfindingState {
 pub(crate) enum PathfindingErrors {
     Unreachable,
 }
+
+#[test]
+fn test_hierarchical_path() {
+    use map_generation::testing;
+
+    // a floor across three chunks, split by a wall with a single gap
+    let mut world_map = testing::floor(0..48, 0..16);
+    testing::wall(&mut world_map, (1..16).map(|y| IVec2::new(24, y)));
+    let mut graph = ChunkGraph::default();
+    let start = IWorldCoordinates(IVec3::new(2, 14, 501));
+    let target = IWorldCoordinates(IVec3::new(45, 14, 501));
+    let search = |world_map: &mut WorldMap, graph: &mut ChunkGraph| {
+        let mut pathfinder = Pathfinder::new(start, vec![target]);
+        loop {
+            match pathfinder.calculate_step(world_map, graph) {
+                PathfindingState::Calculating => (),
+                PathfindingState::Complete(_) => return Some(pathfinder.to_path(target.0)),
+                PathfindingState::Failed(_) => return None,
+            }
+        }
+    };
+
+    let path = search(&mut world_map, &mut graph).unwrap();
+    assert_eq!(path.first(), Some(&start));
+    assert_eq!(path.last(), Some(&target));
+    assert!(path.contains(&IWorldCoordinates(IVec3::new(24, 0, 501))));
+    for step in path.windows(2) {
+        assert!(step[0].adjacency(&step[1]).is_some(), "{:?}", step);
+        assert!(world_map.can_move(step[0].0, step[1].0));
+        assert!(world_map.footing(step[1].0).is_some());
+    }
+
+    // closing the gap drops the cached paths through it
+    testing::wall(&mut world_map, [IVec2::new(24, 0)]);
+    graph.invalidate(IWorldCoordinates(IVec3::new(24, 0, 501)));
+    assert!(search(&mut world_map, &mut graph).is_none());
+}
+
+#[test]
+fn test_shortest_path() {
+    use map_generation::{
+        block_type::{BlockType, SolidMaterial},
+        testing::{self, STONE as stone},
+    };
+    use std::f32::consts::SQRT_2;
+
+    let granite = SolidMaterial::Granite.traversal_cost();
+    // a floor filling a single chunk, for paths across chunks see `test_path_across_chunks`
+    let mut world_map = testing::floor(0..16, 0..16);
+    let cost = |world_map: &mut WorldMap, start: IVec3, target: IVec3| {
+        let mut graph = ChunkGraph::default();
+        let mut pathfinder =
+            Pathfinder::new(IWorldCoordinates(start), vec![IWorldCoordinates(target)]);
+        loop {
+            match pathfinder.calculate_step(world_map, &mut graph) {
+                PathfindingState::Calculating => (),
+                PathfindingState::Complete(_) => break,
+                PathfindingState::Failed(_) => panic!("no path from {start} to {target}"),
+            }
+        }
+        pathfinder
+            .to_path(target)
+            .windows(2)
+            .map(|step| world_map.move_cost(step[0].0, step[1].0).unwrap())
+            .sum::<f32>()
+    };
+    let assert_cost = |actual: f32, expected: f32| {
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    };
+
+    // diagonals first, then straight on
+    assert_cost(
+        cost(
+            &mut world_map,
+            IVec3::new(1, 1, 501),
+            IVec3::new(11, 5, 501),
+        ),
+        granite * (6.0 + 4.0 * SQRT_2),
+    );
+
+    // a plateau that has to be crossed, one layer up and down again
+    for x in 5..8 {
+        for y in 0..16 {
+            world_map.set_block(IWorldCoordinates(IVec3::new(x, y, 501)), stone);
+        }
+    }
+    assert_cost(
+        cost(
+            &mut world_map,
+            IVec3::new(2, 5, 501),
+            IVec3::new(11, 5, 501),
+        ),
+        granite * (9.0 + 2.0 * map_generation::movement::CLIMB_COST),
+    );
+
+    // a wall too high to climb with a gap at its northern end
+    for x in 5..8 {
+        for y in 0..16 {
+            world_map.set_block(IWorldCoordinates(IVec3::new(x, y, 501)), BlockType::None);
+        }
+    }
+    testing::wall(&mut world_map, (0..14).map(|y| IVec2::new(8, y)));
+    let start = IVec3::new(2, 2, 501);
+    let target = IVec3::new(13, 2, 501);
+    assert_cost(
+        cost(&mut world_map, start, target),
+        granite * (13.0 + 11.0 * SQRT_2),
+    );
+
+    // the heuristic never overestimates
+    let blocks: Vec<IVec3> = (0..16)
+        .flat_map(|x| (0..16).map(move |y| IVec3::new(x, y, 501)))
+        .filter(|block| world_map.footing(*block).is_some())
+        .collect();
+    let chunk = IWorldCoordinates(start).chunk();
+    for (_, edge) in search_within(&world_map, chunk, &[start], &blocks, false) {
+        let pathfinder =
+            Pathfinder::new(IWorldCoordinates(start), vec![IWorldCoordinates(edge.to)]);
+        assert!(pathfinder.heuristic(start) <= edge.cost, "{:?}", edge.to);
+    }
+}
+
+#[test]
+fn test_path_across_chunks() {
+    use map_generation::{block_type::SolidMaterial, movement::distance, testing};
+    use std::f32::consts::SQRT_2;
+
+    let granite = SolidMaterial::Granite.traversal_cost();
+    let cost = |world_map: &mut WorldMap, start: IVec3, target: IVec3| {
+        let mut graph = ChunkGraph::default();
+        let mut pathfinder =
+            Pathfinder::new(IWorldCoordinates(start), vec![IWorldCoordinates(target)]);
+        // every step prepares a single chunk at most
+        let prepared = |world_map: &WorldMap, graph: &ChunkGraph| {
+            world_map.resident_chunks() + graph.regions().chunks() + graph.chunks()
+        };
+        loop {
+            let before = (prepared(world_map, &graph), pathfinder.prepared);
+            let state = pathfinder.calculate_step(world_map, &mut graph);
+            assert!(prepared(world_map, &graph) <= before.0 + 1);
+            assert!(pathfinder.prepared <= before.1 + 1);
+            match state {
+                PathfindingState::Calculating => (),
+                PathfindingState::Complete(_) => break,
+                PathfindingState::Failed(_) => panic!("no path from {start} to {target}"),
+            }
+        }
+        pathfinder
+            .to_path(target)
+            .windows(2)
+            .map(|step| world_map.move_cost(step[0].0, step[1].0).unwrap())
+            .sum::<f32>()
+    };
+    let assert_cost = |actual: f32, expected: f32| {
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    };
+
+    // an open floor over three by three chunks
+    let mut world_map = testing::floor(0..48, 0..48);
+    for (start, target) in [
+        (IVec3::new(1, 1, 501), IVec3::new(46, 46, 501)),
+        (IVec3::new(2, 40, 501), IVec3::new(45, 3, 501)),
+        (IVec3::new(7, 20, 501), IVec3::new(40, 27, 501)),
+        (IVec3::new(15, 15, 501), IVec3::new(16, 16, 501)),
+        (IVec3::new(1, 1, 501), IVec3::new(30, 2, 501)),
+    ] {
+        assert_cost(
+            cost(&mut world_map, start, target),
+            granite * distance(start, target),
+        );
+    }
+
+    // a wall behind the border with a gap at its northern end, so the cheapest path crosses the
+    // border far from its middle
+    let mut world_map = testing::floor(0..48, 0..16);
+    testing::wall(&mut world_map, (0..14).map(|y| IVec2::new(18, y)));
+    assert_cost(
+        cost(
+            &mut world_map,
+            IVec3::new(2, 2, 501),
+            IVec3::new(30, 2, 501),
+        ),
+        granite * (4.0 + 24.0 * SQRT_2),
+    );
+}
+
+#[test]
+fn test_nearest_goal() {
+    use map_generation::testing;
+
+    let mut world_map = testing::floor(0..32, 0..16);
+    let nearest = |world_map: &mut WorldMap, goals: &[IVec3]| {
+        let mut graph = ChunkGraph::default();
+        let mut pathfinder = Pathfinder::new(
+            IWorldCoordinates(IVec3::new(2, 2, 501)),
+            goals.iter().copied().map(IWorldCoordinates).collect(),
+        );
+        loop {
+            match pathfinder.calculate_step(world_map, &mut graph) {
+                PathfindingState::Calculating => (),
+                PathfindingState::Complete(path) => return path.destination().0,
+                PathfindingState::Failed(_) => panic!("no path to {goals:?}"),
+            }
+        }
+    };
+
+    // a goal in another chunk, another in the same one and one nobody can stand in
+    let far = IVec3::new(20, 2, 501);
+    let near = IVec3::new(2, 9, 501);
+    let floating = IVec3::new(3, 2, 503);
+    assert_eq!(nearest(&mut world_map, &[far, near, floating]), near);
+
+    // walling off the near goal makes the far one the nearest
+    testing::wall(&mut world_map, (0..16).map(|x| IVec2::new(x, 6)));
+    assert_eq!(nearest(&mut world_map, &[far, near, floating]), far);
+
+    // of several goals in one chunk, the closest is reached
+    let farther = IVec3::new(28, 12, 501);
+    assert_eq!(nearest(&mut world_map, &[farther, far]), far);
+    assert_eq!(nearest(&mut world_map, &[farther]), farther);
+}
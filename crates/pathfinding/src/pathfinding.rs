@@ -1,32 +1,163 @@
-use bevy::prelude::*;
-use common::traits::AddNamedObserver;
-use map_generation::world_map::WorldMap;
+use std::cmp::Reverse;
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, futures::check_ready},
+};
+use common::{states::AppState, traits::AddNamedObserver};
+use map_generation::{residency::ChunkPins, world_map::WorldMap};
 
 use crate::{
     PathEvent, PathState, PathfindingCalculation, PathfindingCalculationEvent,
-    path::{self, Path},
+    budget::{CHUNK_COST, PathPriority, PathfindingBudget, PathfindingStats},
+    hierarchy::{self, ChunkGraph},
+    path::{self, Path, Replanning},
     pathfinder::{Pathfinder, PathfinderListener, PathfindingErrors, PathfindingState},
 };
 
 pub fn plugin(app: &mut App) {
     app.register_type::<Pathfinder>()
         .register_type::<Path>()
-        .add_systems(Update, calculate_path.run_if(resource_exists::<WorldMap>))
+        .register_type::<PathfindingBudget>()
+        .register_type::<PathPriority>()
+        .register_type::<PathfindingStats>()
+        .init_resource::<PathfindingBudget>()
+        .init_resource::<PathfindingStats>()
+        .init_resource::<ChunkGraph>()
+        .add_systems(OnEnter(AppState::MainGame), reset_graph)
+        .add_systems(
+            Update,
+            (
+                hierarchy::invalidate,
+                path::check_paths,
+                finish_searches,
+                start_searches,
+            )
+                .chain()
+                .run_if(resource_exists::<WorldMap>),
+        )
         .add_systems(
             Update,
             (path::tick_path, path::follow_path, check_pathfinder).chain(),
         )
-        .add_named_observer(listen_for_path, "listen_for_path");
+        .add_named_observer(listen_for_path, "listen_for_path")
+        .add_named_observer(path::pin_path, "pin_path")
+        .add_named_observer(path::unpin_path, "unpin_path");
 }
 
-fn calculate_path(
+/// A new world starts with an empty graph.
+fn reset_graph(mut commands: Commands) {
+    commands.insert_resource(ChunkGraph::default());
+}
+
+/// A batch of node expansions of a [`Pathfinder`], running on the [`AsyncComputeTaskPool`].
+///
+/// While it runs, the task owns the [`Pathfinder`], it is taken off the entity and put back once
+/// the batch is done.
+#[derive(Component)]
+pub(crate) struct PathfindingTask(Task<(Pathfinder, WorldMap, ChunkGraph, PathfindingState)>);
+
+/// Pathfinders, whether a batch of them is running or not.
+type Searching = Or<(With<Pathfinder>, With<PathfindingTask>)>;
+
+/// Hands out the [`PathfindingBudget`] to the pathfinders that aren't searching right now and
+/// starts a task for each of them. All tasks share a snapshot of the map and the graph, taken
+/// once per frame.
+fn start_searches(
+    budget: Res<PathfindingBudget>,
+    mut stats: ResMut<PathfindingStats>,
     world_map: Res<WorldMap>,
-    mut query: Query<(Entity, &mut Pathfinder)>,
+    graph: Res<ChunkGraph>,
+    query: Query<(Entity, Option<&ChildOf>, Has<PathfindingTask>), Searching>,
+    priorities: Query<(Option<&PathPriority>, Has<Replanning>)>,
+    mut commands: Commands,
+) {
+    stats.pending = query.iter().count();
+    let mut idle: Vec<(Entity, u32)> = query
+        .iter()
+        .filter(|(_, _, searching)| !searching)
+        .map(|(entity, parent, _)| {
+            let priority = parent
+                .and_then(|parent| priorities.get(parent.parent()).ok())
+                .map(|(priority, replanning)| {
+                    let priority = priority.copied().unwrap_or_default();
+                    if replanning {
+                        priority.raised()
+                    } else {
+                        priority
+                    }
+                })
+                .unwrap_or_default();
+            (entity, priority.weight())
+        })
+        .collect();
+    // the highest priorities go first, in case the budget runs out
+    idle.sort_by_key(|(_, weight)| Reverse(*weight));
+    let total_weight: u32 = idle.iter().map(|(_, weight)| weight).sum();
+
+    let snapshot = (world_map.clone(), graph.clone());
+    // what is left after labelling the regions the map changes dropped
+    let available = budget
+        .expansions_per_frame
+        .saturating_sub(stats.relabelled as u32 * CHUNK_COST);
+    let mut expansions = 0;
+    for (entity, weight) in idle {
+        if expansions >= available {
+            break;
+        }
+        // everyone gets a share of the budget, but at least a single expansion
+        let share = (available * weight / total_weight).max(1);
+        expansions += share;
+        let (mut world_map, mut graph) = snapshot.clone();
+        commands
+            .entity(entity)
+            .queue_silenced(move |mut entity: EntityWorldMut| {
+                let Some(mut pathfinder) = entity.take::<Pathfinder>() else {
+                    return;
+                };
+                let task = AsyncComputeTaskPool::get().spawn(async move {
+                    // what the last batch spent too much is paid off first
+                    let mut spent = pathfinder.overspent;
+                    let mut state = PathfindingState::Calculating;
+                    while spent < share {
+                        let prepared = pathfinder.prepared;
+                        state = pathfinder.calculate_step(&mut world_map, &mut graph);
+                        spent += 1 + CHUNK_COST * (pathfinder.prepared - prepared);
+                        if !matches!(state, PathfindingState::Calculating) {
+                            break;
+                        }
+                    }
+                    pathfinder.overspent = spent.saturating_sub(share);
+                    (pathfinder, world_map, graph, state)
+                });
+                entity.insert(PathfindingTask(task));
+            });
+    }
+    stats.expansions = expansions;
+}
+
+/// Puts the pathfinders of finished tasks back, keeps the chunks they added and reports the paths
+/// they found.
+fn finish_searches(
+    mut stats: ResMut<PathfindingStats>,
+    mut world_map: ResMut<WorldMap>,
+    mut graph: ResMut<ChunkGraph>,
+    mut query: Query<(Entity, &mut PathfindingTask)>,
     mut commands: Commands,
 ) {
-    for (entity, mut path) in &mut query {
-        match path.calculate_step(world_map.as_ref()) {
-            PathfindingState::Calculating => (),
+    for (entity, mut task) in &mut query {
+        let Some((pathfinder, map_clone, graph_clone, state)) = check_ready(&mut task.0) else {
+            continue;
+        };
+        commands.entity(entity).remove::<PathfindingTask>();
+        world_map.merge(map_clone);
+        graph.merge(graph_clone);
+        match state {
+            PathfindingState::Calculating => {
+                // keeps the chunks the search has loaded until it is done
+                let pins = ChunkPins::surrounding(pathfinder.reached());
+                commands.entity(entity).insert((pathfinder, pins));
+            }
             PathfindingState::Failed(err) => match err {
                 PathfindingErrors::Unreachable => {
                     debug!("pathfinding failed");
@@ -34,8 +165,8 @@ fn calculate_path(
                         entity,
                         calculation: PathfindingCalculation::Failed,
                     });
-
                     commands.entity(entity).despawn();
+                    stats.failed += 1;
                 }
             },
             PathfindingState::Complete(path) => {
@@ -45,10 +176,13 @@ fn calculate_path(
                     calculation: PathfindingCalculation::Succeeded(path),
                 });
                 commands.entity(entity).despawn();
+                stats.completed += 1;
             }
         }
     }
+    stats.graph_chunks = graph.chunks();
 }
+
 fn listen_for_path(
     trigger: On<PathfindingCalculationEvent>,
     listeners: Query<&PathfinderListener>,
@@ -65,7 +199,7 @@ fn listen_for_path(
             );
             commands
                 .entity(trigger.entity)
-                .remove::<PathfinderListener>()
+                .remove::<(PathfinderListener, Replanning)>()
                 .insert(path.clone());
         }
     }
@@ -73,7 +207,7 @@ fn listen_for_path(
 
 fn check_pathfinder(
     listeners: Query<(Entity, Option<&Children>), With<PathfinderListener>>,
-    pathfinders: Query<Entity, With<Pathfinder>>,
+    pathfinders: Query<Entity, Searching>,
     mut commands: Commands,
 ) {
     for (parent, children) in listeners {
@@ -89,9 +223,10 @@ fn check_pathfinder(
                 "entity {} has no more Pathfinder children, removing listener...",
                 parent
             );
+            // a blocked path that couldn't be replaced isn't followed any further
             commands
                 .entity(parent)
-                .remove::<PathfinderListener>()
+                .remove::<(PathfinderListener, Replanning, Path)>()
                 .trigger(|entity| PathEvent {
                     entity,
                     state: PathState::CalculationFailed,
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
-use common::{traits::Neighbors, types::IWorldCoordinates};
-use map_generation::{block_type::BlockType, world_map::WorldMap};
+use common::traits::Neighbors;
+use map_generation::world_map::WorldMap;
 
 pub(crate) trait PathfindingMap {
     fn get_neighbors(&self, coordinates: IVec3) -> impl Iterator<Item = (IVec3, f32)>;
@@ -8,23 +8,14 @@ pub(crate) trait PathfindingMap {
 
 impl PathfindingMap for WorldMap {
     fn get_neighbors(&self, coordinates: IVec3) -> impl Iterator<Item = (IVec3, f32)> {
+        let bounds = self.bounds();
         coordinates
             .all_neighbors()
             .into_iter()
-            .filter_map(|(neighbor, squared_distance)| {
-                let next_block = self.get_block(IWorldCoordinates(neighbor))?;
-                let block_below = self.get_block(IWorldCoordinates(neighbor - IVec3::Z))?;
-                if next_block != BlockType::None {
-                    return None;
-                }
-                let BlockType::Solid(material) = block_below else {
-                    return None;
-                };
-
-                Some((
-                    neighbor,
-                    material.traversal_cost() * squared_distance as f32,
-                ))
+            // nobody leaves the map
+            .filter(move |(neighbor, _)| bounds.contains(*neighbor))
+            .filter_map(move |(neighbor, _)| {
+                Some((neighbor, self.move_cost(coordinates, neighbor)?))
             })
     }
 }
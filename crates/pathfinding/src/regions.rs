@@ -0,0 +1,385 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use common::{
+    constants::CHUNK_SIZE,
+    coordinates::{ChunkBlockCoordinates, ChunkCoordinates, IWorldCoordinates},
+};
+use map_generation::world_map::WorldMap;
+
+use crate::pathfinding_map::PathfindingMap;
+
+/// How many chunks a region may span before it counts as open, see [`Regions::reachable`].
+const MAX_CHUNKS: usize = 32;
+
+/// The connected regions of the blocks entities can stand in.
+///
+/// Every chunk is split into regions, the blocks that can be reached from each other without
+/// leaving the chunk. Regions lead into regions of the neighboring chunks wherever entities can
+/// cross the border. Chunks are labelled the first time a search reaches them. They are dropped
+/// together with the chunks of the [`crate::ChunkGraph`] that holds them and labelled again over
+/// the next frames, see [`Regions::relabel`].
+#[derive(Clone, Default)]
+pub struct Regions {
+    chunks: Arc<HashMap<IVec3, Arc<ChunkRegions>>>,
+    /// Chunks that were dropped and are waiting to be labelled again.
+    dropped: VecDeque<IVec3>,
+}
+
+/// What a single step of exploring the regions found out, see [`Regions::explore_step`].
+pub(crate) enum Exploration {
+    /// As much as [`Regions::reachable`] tells.
+    Done(Option<bool>),
+    /// The chunk was worked on, more steps are needed.
+    Pending(ChunkCoordinates),
+}
+
+/// The regions reached by flooding from a block, see [`Regions::reach`].
+pub struct Reach {
+    regions: HashSet<Region>,
+    /// Whether every region connected to the block is known.
+    complete: bool,
+}
+
+/// The regions of a single chunk.
+struct ChunkRegions {
+    /// The region of every block of the chunk, 0 for blocks nobody can stand in.
+    labels: Vec<u16>,
+    /// The blocks in other chunks each region leads to.
+    links: HashMap<u16, Vec<IVec3>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Region {
+    chunk: IVec3,
+    label: u16,
+}
+
+impl Regions {
+    /// Returns whether any of the goals can be reached from `from`, as far as the chunks labelled
+    /// so far tell.
+    ///
+    /// Returns None if that isn't known: the region of `from` leads into chunks that weren't
+    /// labelled yet, or it spans more than [`MAX_CHUNKS`] chunks and is treated as open.
+    pub fn reachable(
+        &self,
+        from: IWorldCoordinates,
+        goals: impl IntoIterator<Item = IWorldCoordinates>,
+    ) -> Option<bool> {
+        flood(from, goals, |chunk| self.chunks.get(&chunk.0).cloned())
+    }
+
+    /// Floods the regions from `from` once, to tell for any number of goals whether they can be
+    /// reached, see [`Reach::reaches`]. Returns None if nobody can stand at `from` or its chunk
+    /// isn't labelled.
+    pub fn reach(&self, from: IWorldCoordinates) -> Option<Reach> {
+        let mut chunks = HashMap::default();
+        let mut load = |chunk: ChunkCoordinates| self.chunks.get(&chunk.0).cloned();
+        let start = region_of(&mut chunks, &mut load, from)??;
+        Some(match spread(start, &mut chunks, &mut load, |_| false) {
+            Spread::Found => unreachable!("nothing is looked for"),
+            Spread::Exhausted(regions) => Reach {
+                regions,
+                complete: true,
+            },
+            Spread::Open(regions) => Reach {
+                regions,
+                complete: false,
+            },
+        })
+    }
+
+    /// Like [`Regions::reachable`], but labels every chunk it needs.
+    pub fn explore(
+        &mut self,
+        world_map: &mut WorldMap,
+        from: IWorldCoordinates,
+        goals: impl IntoIterator<Item = IWorldCoordinates>,
+    ) -> Option<bool> {
+        flood(from, goals, |chunk| {
+            if let Some(regions) = self.chunks.get(&chunk.0) {
+                return Some(regions.clone());
+            }
+            // the blocks around the chunk decide where it can be left
+            world_map.ensure_surrounding_exist(chunk);
+            let regions = Arc::new(ChunkRegions::label(world_map, chunk));
+            Arc::make_mut(&mut self.chunks).insert(chunk.0, regions.clone());
+            Some(regions)
+        })
+    }
+
+    /// Like [`Regions::explore`], but does no more than a single chunk's worth of work: generates
+    /// one of the chunks around the first chunk that isn't labelled yet, or labels it.
+    pub(crate) fn explore_step(
+        &mut self,
+        world_map: &mut WorldMap,
+        from: IWorldCoordinates,
+        goals: impl IntoIterator<Item = IWorldCoordinates>,
+    ) -> Exploration {
+        let mut worked_on = None;
+        // the flood stops at the first chunk that isn't labelled yet
+        let reachable = flood(from, goals, |chunk| {
+            if let Some(regions) = self.chunks.get(&chunk.0) {
+                return Some(regions.clone());
+            }
+            if !world_map.generate_surrounding(chunk) {
+                let regions = Arc::new(ChunkRegions::label(world_map, chunk));
+                Arc::make_mut(&mut self.chunks).insert(chunk.0, regions);
+            }
+            worked_on = Some(chunk);
+            None
+        });
+        match worked_on {
+            Some(chunk) => Exploration::Pending(chunk),
+            None => Exploration::Done(reachable),
+        }
+    }
+
+    /// Returns how many chunks are labelled.
+    pub fn chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub(crate) fn invalidate(&mut self, chunk: IVec3) {
+        if self.chunks.contains_key(&chunk) {
+            Arc::make_mut(&mut self.chunks).remove(&chunk);
+            if !self.dropped.contains(&chunk) {
+                self.dropped.push_back(chunk);
+            }
+        }
+    }
+
+    /// Labels up to `limit` of the dropped chunks again and returns how many it labelled.
+    ///
+    /// Chunks a search has labelled in the meantime are skipped, as are those whose surroundings
+    /// aren't in memory anymore. A search labels them once it reaches them.
+    pub(crate) fn relabel(&mut self, world_map: &WorldMap, limit: usize) -> usize {
+        let mut relabelled = 0;
+        while relabelled < limit
+            && let Some(chunk) = self.dropped.pop_front()
+        {
+            if self.chunks.contains_key(&chunk)
+                || !world_map.is_surrounding_resident(ChunkCoordinates(chunk))
+            {
+                continue;
+            }
+            let regions = Arc::new(ChunkRegions::label(world_map, ChunkCoordinates(chunk)));
+            Arc::make_mut(&mut self.chunks).insert(chunk, regions);
+            relabelled += 1;
+        }
+        relabelled
+    }
+
+    /// Adds the chunks a clone has labelled, unless they are `stale`.
+    pub(crate) fn merge(&mut self, clone: Regions, stale: impl Fn(IVec3) -> bool) {
+        if Arc::ptr_eq(&self.chunks, &clone.chunks) {
+            return;
+        }
+        for (chunk, regions) in clone.chunks.iter() {
+            if !stale(*chunk) && !self.chunks.contains_key(chunk) {
+                Arc::make_mut(&mut self.chunks).insert(*chunk, regions.clone());
+            }
+        }
+    }
+}
+
+impl Reach {
+    /// Returns whether any of the goals can be reached, like [`Regions::reachable`] would.
+    pub fn reaches(
+        &self,
+        regions: &Regions,
+        goals: impl IntoIterator<Item = IWorldCoordinates>,
+    ) -> Option<bool> {
+        let mut chunks = HashMap::default();
+        let mut load = |chunk: ChunkCoordinates| regions.chunks.get(&chunk.0).cloned();
+        let mut goal_regions = HashSet::new();
+        for goal in goals {
+            goal_regions.extend(region_of(&mut chunks, &mut load, goal)?);
+        }
+        if goal_regions
+            .iter()
+            .any(|region| self.regions.contains(region))
+        {
+            Some(true)
+        } else if self.complete || goal_regions.is_empty() {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+impl ChunkRegions {
+    /// Labels the regions of a chunk. The chunks around it have to be in memory.
+    fn label(world_map: &WorldMap, chunk: ChunkCoordinates) -> Self {
+        let mut labels = vec![0; CHUNK_SIZE.element_product() as usize];
+        let mut links: HashMap<u16, Vec<IVec3>> = HashMap::default();
+        let mut next_label = 0;
+        for x in 0..CHUNK_SIZE.x {
+            for y in 0..CHUNK_SIZE.y {
+                for z in 0..CHUNK_SIZE.z {
+                    let block = chunk.block((x, y, z));
+                    if labels[index(block.chunk_block())] != 0
+                        || world_map.footing(block.0).is_none()
+                    {
+                        continue;
+                    }
+                    next_label += 1;
+                    labels[index(block.chunk_block())] = next_label;
+                    let mut open = vec![block.0];
+                    while let Some(current) = open.pop() {
+                        for (neighbor, _) in world_map.get_neighbors(current) {
+                            let neighbor = IWorldCoordinates(neighbor);
+                            if neighbor.chunk() != chunk {
+                                links.entry(next_label).or_default().push(neighbor.0);
+                                continue;
+                            }
+                            let label = &mut labels[index(neighbor.chunk_block())];
+                            if *label == 0 {
+                                *label = next_label;
+                                open.push(neighbor.0);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for blocks in links.values_mut() {
+            blocks.sort_by_key(|block| block.to_array());
+            blocks.dedup();
+        }
+        ChunkRegions { labels, links }
+    }
+}
+
+fn index(block: ChunkBlockCoordinates) -> usize {
+    (block.0.x + block.0.y * CHUNK_SIZE.x + block.0.z * CHUNK_SIZE.x * CHUNK_SIZE.y) as usize
+}
+
+/// Returns the region of a block, loading its chunk with `load`. None if the chunk can't be
+/// loaded, Some(None) if nobody can stand in the block.
+fn region_of(
+    chunks: &mut HashMap<IVec3, Arc<ChunkRegions>>,
+    load: &mut impl FnMut(ChunkCoordinates) -> Option<Arc<ChunkRegions>>,
+    coordinates: IWorldCoordinates,
+) -> Option<Option<Region>> {
+    let (chunk, block) = coordinates.to_chunk_and_block();
+    if !chunks.contains_key(&chunk.0) {
+        chunks.insert(chunk.0, load(chunk)?);
+    }
+    let label = chunks[&chunk.0].labels[index(block)];
+    Some((label != 0).then_some(Region {
+        chunk: chunk.0,
+        label,
+    }))
+}
+
+/// How far [`spread`] got.
+enum Spread {
+    /// A region it was looking for.
+    Found,
+    /// Every region connected to the start, which are all known.
+    Exhausted(HashSet<Region>),
+    /// Some of the regions connected to the start, before it ran into a chunk that couldn't be
+    /// loaded or spanned more than [`MAX_CHUNKS`] chunks.
+    Open(HashSet<Region>),
+}
+
+/// Floods the regions from `start` until `found` returns true for one of them.
+fn spread(
+    start: Region,
+    chunks: &mut HashMap<IVec3, Arc<ChunkRegions>>,
+    load: &mut impl FnMut(ChunkCoordinates) -> Option<Arc<ChunkRegions>>,
+    found: impl Fn(&Region) -> bool,
+) -> Spread {
+    let mut visited = HashSet::from([start]);
+    let mut spanned = HashSet::from([start.chunk]);
+    let mut open = VecDeque::from([start]);
+    while let Some(current) = open.pop_front() {
+        if found(&current) {
+            return Spread::Found;
+        }
+        let links = chunks[&current.chunk]
+            .links
+            .get(&current.label)
+            .cloned()
+            .unwrap_or_default();
+        for block in links {
+            let Some(next) = region_of(chunks, load, IWorldCoordinates(block)) else {
+                return Spread::Open(visited);
+            };
+            let Some(next) = next else {
+                continue;
+            };
+            if visited.insert(next) {
+                spanned.insert(next.chunk);
+                open.push_back(next);
+            }
+        }
+        if spanned.len() > MAX_CHUNKS {
+            return Spread::Open(visited);
+        }
+    }
+    Spread::Exhausted(visited)
+}
+
+/// Floods the regions from `from` until one of the goals is found, loading chunks with `load`.
+fn flood(
+    from: IWorldCoordinates,
+    goals: impl IntoIterator<Item = IWorldCoordinates>,
+    mut load: impl FnMut(ChunkCoordinates) -> Option<Arc<ChunkRegions>>,
+) -> Option<bool> {
+    let mut chunks: HashMap<IVec3, Arc<ChunkRegions>> = HashMap::default();
+    let mut goal_regions = HashSet::new();
+    for goal in goals {
+        goal_regions.extend(region_of(&mut chunks, &mut load, goal)?);
+    }
+    // entities that don't stand anywhere are left to the search
+    let start = region_of(&mut chunks, &mut load, from)??;
+    if goal_regions.is_empty() {
+        return Some(false);
+    }
+    match spread(start, &mut chunks, &mut load, |region| {
+        goal_regions.contains(region)
+    }) {
+        Spread::Found => Some(true),
+        Spread::Exhausted(_) => Some(false),
+        Spread::Open(_) => None,
+    }
+}
+
+#[test]
+fn test_sealed_room() {
+    use map_generation::{block_type::BlockType, testing};
+
+    // a floor across two chunks with a room walled in on the second one
+    let mut world_map = testing::floor(0..32, 0..16);
+    testing::room(&mut world_map, IRect::new(20, 4, 24, 8));
+    let from = IWorldCoordinates(IVec3::new(2, 2, 501));
+    let inside = IWorldCoordinates(IVec3::new(22, 6, 501));
+    let outside = IWorldCoordinates(IVec3::new(28, 12, 501));
+
+    let mut regions = Regions::default();
+    assert_eq!(regions.reachable(from, [inside]), None);
+    assert_eq!(regions.explore(&mut world_map, from, [outside]), Some(true));
+    assert_eq!(regions.explore(&mut world_map, from, [inside]), Some(false));
+    // the labelled chunks are enough to tell now
+    assert_eq!(regions.reachable(from, [inside, outside]), Some(true));
+    assert_eq!(regions.reachable(from, [inside]), Some(false));
+    let reach = regions.reach(from).unwrap();
+    assert_eq!(reach.reaches(&regions, [inside]), Some(false));
+    assert_eq!(reach.reaches(&regions, [outside]), Some(true));
+
+    // opening the wall connects the room
+    for z in 501..503 {
+        world_map.set_block(IWorldCoordinates(IVec3::new(20, 6, z)), BlockType::None);
+    }
+    regions.invalidate(IWorldCoordinates(IVec3::new(20, 6, 501)).chunk().0);
+    assert_eq!(regions.reachable(from, [inside]), None);
+    assert_eq!(regions.relabel(&world_map, usize::MAX), 1);
+    assert_eq!(regions.reachable(from, [inside]), Some(true));
+    assert_eq!(regions.explore(&mut world_map, from, [inside]), Some(true));
+}
@@ -0,0 +1,3 @@
+pub mod save_game;
+
+pub use save_game::*;
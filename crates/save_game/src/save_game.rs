@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use camera::CameraLayer;
+use common::{
+    coordinates::WorldCoordinates,
+    ron_file::{read, write},
+    states::AppState,
+    traits::AddNamedObserver,
+};
+use dwarf::Dwarf;
+use leafwing_input_manager::{
+    Actionlike,
+    plugin::InputManagerPlugin,
+    prelude::{ActionState, InputMap},
+};
+use map_generation::{
+    map_generation::WorldGenerationSettings,
+    world_map::{WorldMap, WorldMapSave},
+};
+use serde::{Deserialize, Serialize};
+use work::{Digger, WorkOrder, work_order_queue::WorkOrderQueue};
+
+const SAVE_PATH: &str = "saves/quicksave.ron";
+
+pub fn plugin(app: &mut App) {
+    app.add_plugins(InputManagerPlugin::<SaveControls>::default())
+        .add_systems(
+            OnEnter(AppState::MainGame),
+            (
+                setup,
+                restore_entities.run_if(resource_exists::<LoadedGame>),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                save.run_if(resource_exists::<WorldMap>),
+                restore_camera.run_if(resource_exists::<LoadedGame>),
+            )
+                .run_if(in_state(AppState::MainGame)),
+        )
+        .add_named_observer(on_load_game, "on_load_game");
+}
+
+/// Trigger this to load the last save and continue the game from there.
+#[derive(Event)]
+pub struct LoadGame;
+
+/// Everything needed to continue a game where it was left off.
+#[derive(Deserialize, Serialize)]
+struct SaveGame {
+    settings: WorldGenerationSettings,
+    world_map: WorldMapSave,
+    dwarves: Vec<DwarfSave>,
+    work_orders: Vec<WorkOrder>,
+    camera: CameraSave,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct DwarfSave {
+    coordinates: WorldCoordinates,
+    digger: Digger,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CameraSave {
+    translation: Vec3,
+    layer: i32,
+}
+
+/// Holds the parts of a save that have to be restored after entering [`AppState::MainGame`].
+///
+/// As long as this resource exists, a save game is being restored.
+#[derive(Resource)]
+pub struct LoadedGame {
+    dwarves: Vec<DwarfSave>,
+    work_orders: Vec<WorkOrder>,
+    camera: CameraSave,
+}
+
+#[derive(Actionlike, Clone, Debug, Eq, Hash, PartialEq, Reflect)]
+enum SaveControls {
+    QuickSave,
+}
+
+/// Returns true if there is a save that can be loaded.
+pub fn save_exists() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+fn setup(mut commands: Commands) {
+    let input_map = InputMap::default().with(SaveControls::QuickSave, KeyCode::F5);
+    commands.spawn((
+        Name::new("Save Controls"),
+        input_map,
+        DespawnOnExit(AppState::MainGame),
+    ));
+}
+
+fn save(
+    query: Single<&ActionState<SaveControls>>,
+    world_generation_settings: Res<WorldGenerationSettings>,
+    world_map: Res<WorldMap>,
+    work_order_queue: Res<WorkOrderQueue>,
+    dwarves: Query<(&WorldCoordinates, &Digger), With<Dwarf>>,
+    camera: Single<(&Transform, &CameraLayer), With<Camera2d>>,
+) {
+    if !query.just_pressed(&SaveControls::QuickSave) {
+        return;
+    }
+    let (camera_transform, camera_layer) = camera.into_inner();
+    let save_game = SaveGame {
+        settings: world_generation_settings.clone(),
+        world_map: world_map.save(),
+        dwarves: dwarves
+            .iter()
+            .map(|(coordinates, digger)| DwarfSave {
+                coordinates: *coordinates,
+                digger: *digger,
+            })
+            .collect(),
+        work_orders: work_order_queue.iter().copied().collect(),
+        camera: CameraSave {
+            translation: camera_transform.translation,
+            layer: camera_layer.0,
+        },
+    };
+    match write(Path::new(SAVE_PATH), &save_game) {
+        Ok(()) => info!("saved game to {}", SAVE_PATH),
+        Err(err) => error!("failed to save game to {}: {}", SAVE_PATH, err),
+    }
+}
+
+fn on_load_game(
+    _trigger: On<LoadGame>,
+    mut world_generation_settings: ResMut<WorldGenerationSettings>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut commands: Commands,
+) {
+    let save_game: SaveGame = match read(Path::new(SAVE_PATH)) {
+        Ok(save_game) => save_game,
+        Err(err) => {
+            error!("failed to load game from {}: {}", SAVE_PATH, err);
+            return;
+        }
+    };
+    info!("loading game from {}", SAVE_PATH);
+    *world_generation_settings = save_game.settings;
+    // the world map picks this up when it is spawned
+    commands.insert_resource(save_game.world_map);
+    commands.insert_resource(LoadedGame {
+        dwarves: save_game.dwarves,
+        work_orders: save_game.work_orders,
+        camera: save_game.camera,
+    });
+    next_state.set(AppState::MainGame);
+}
+
+fn restore_entities(
+    loaded_game: Res<LoadedGame>,
+    mut work_order_queue: ResMut<WorkOrderQueue>,
+    mut commands: Commands,
+) {
+    for dwarf in &loaded_game.dwarves {
+        commands.spawn((Dwarf, dwarf.coordinates, dwarf.digger));
+    }
+    // work orders that were in progress are simply picked up again by whoever is free
+    for work_order in &loaded_game.work_orders {
+        let entity = match work_order {
+            WorkOrder::Dig(coordinates) => commands.spawn(WorkOrder::dig(*coordinates)).id(),
+        };
+        work_order_queue.push(entity, *work_order);
+    }
+}
+
+fn restore_camera(
+    loaded_game: Res<LoadedGame>,
+    camera: Single<(&mut Transform, &mut CameraLayer), With<Camera2d>>,
+    mut commands: Commands,
+) {
+    let (mut camera_transform, mut camera_layer) = camera.into_inner();
+    camera_transform.translation = loaded_game.camera.translation;
+    camera_layer.0 = loaded_game.camera.layer;
+    commands.remove_resource::<LoadedGame>();
+}
+
+#[test]
+fn test_save_game_round_trip() {
+    use common::coordinates::IWorldCoordinates;
+    use map_generation::testing;
+
+    let mut world_map = testing::floor(0..4, 0..4);
+    world_map.damage_block(IWorldCoordinates(IVec3::new(1, 2, testing::FLOOR)), 0.5);
+    let save_game = SaveGame {
+        settings: WorldGenerationSettings::default(),
+        world_map: world_map.save(),
+        dwarves: vec![DwarfSave {
+            coordinates: WorldCoordinates(Vec3::new(1.5, -2.0, 3.0)),
+            digger: Digger { skill: 1.5 },
+        }],
+        work_orders: vec![WorkOrder::Dig(IWorldCoordinates(IVec3::new(4, 5, 6)))],
+        camera: CameraSave {
+            translation: Vec3::new(10.0, 20.0, 0.0),
+            layer: 6,
+        },
+    };
+    let path = std::env::temp_dir().join("open_fortress_test_save_game.ron");
+    write(&path, &save_game).unwrap();
+    let loaded: SaveGame = read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.dwarves, save_game.dwarves);
+    assert_eq!(loaded.work_orders, save_game.work_orders);
+    assert_eq!(loaded.camera.translation, save_game.camera.translation);
+    assert_eq!(loaded.camera.layer, save_game.camera.layer);
+    // with the deltas of the floor and the state of the damaged block
+    assert_eq!(loaded.world_map, save_game.world_map);
+}
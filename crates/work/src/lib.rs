@@ -1,9 +1,11 @@
 use bevy::prelude::*;
 use common::{
-    traits::SpawnNamedObserver,
-    types::{IWorldCoordinates, WorldCoordinates},
+    coordinates::{IWorldCoordinates, WorldCoordinates},
+    traits::{Neighbors, SpawnNamedObserver},
 };
-use map_generation::messages::BlockUpdate;
+use map_generation::{messages::BlockUpdate, residency::ChunkPins};
+use pathfinding::{ChunkGraph, budget::PathPriority};
+use serde::{Deserialize, Serialize};
 use tasks::{Task, TaskQueue, TaskState};
 use work_order_queue::WorkOrderQueue;
 
@@ -15,6 +17,7 @@ pub mod work_order_queue;
 pub fn plugin(app: &mut App) {
     app.register_type::<WorkOrder>()
         .register_type::<CurrentWorkOrder>()
+        .register_type::<Digger>()
         .add_plugins((tasks::plugin, work_order_queue::plugin))
         .add_systems(
             Update,
@@ -23,7 +26,7 @@ pub fn plugin(app: &mut App) {
 }
 
 /// Represents work orders that can be created by the player
-#[derive(Clone, Component, Copy, Debug, PartialEq, Reflect)]
+#[derive(Clone, Component, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
 pub enum WorkOrder {
     Dig(IWorldCoordinates),
 }
@@ -31,13 +34,29 @@ pub enum WorkOrder {
 impl WorkOrder {
     /// Creates a digging work order for the given world position
     pub fn dig(world_coordinates: IWorldCoordinates) -> impl Bundle {
+        let work_order = WorkOrder::Dig(world_coordinates);
         (
             Name::new(format!("WorkOrder - Dig {}", world_coordinates.0)),
             WorldCoordinates(world_coordinates.0.as_vec3()),
-            WorkOrder::Dig(world_coordinates),
+            // the block and the workplaces stay in memory until the work is done
+            ChunkPins::containing(
+                std::iter::once(world_coordinates).chain(work_order.workplaces()),
+            ),
+            work_order,
         )
     }
 
+    /// Returns the blocks a worker can carry out the work order from
+    fn workplaces(&self) -> Vec<IWorldCoordinates> {
+        match self {
+            WorkOrder::Dig(pos) => pos
+                .same_layer_neighbors()
+                .into_iter()
+                .map(|(coordinates, _)| coordinates)
+                .collect(),
+        }
+    }
+
     /// Creates a TaskQueue from work order
     fn realise(&self) -> impl Bundle {
         match self {
@@ -50,8 +69,36 @@ impl WorkOrder {
 ///
 /// This probably has to be expanded later because not all workers can do all tasks
 #[derive(Component)]
+#[require(Digger, PathPriority)]
 pub struct Worker;
 
+/// How well a worker digs. Every hit deals [`Digger::BASE_DAMAGE`], scaled by the skill of the
+/// worker.
+///
+/// Dwarves keep theirs in save games, everyone else digs like an average digger.
+#[derive(Clone, Component, Copy, Debug, Deserialize, PartialEq, Reflect, Serialize)]
+#[reflect(Component)]
+pub struct Digger {
+    /// 1.0 is an average digger.
+    pub skill: f32,
+}
+
+impl Digger {
+    /// The damage an average digger deals per hit.
+    pub const BASE_DAMAGE: f32 = 0.25;
+
+    /// Returns the damage dealt to a block per hit.
+    pub fn damage(&self) -> f32 {
+        Digger::BASE_DAMAGE * self.skill
+    }
+}
+
+impl Default for Digger {
+    fn default() -> Self {
+        Digger { skill: 1.0 }
+    }
+}
+
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 struct CurrentWorkOrder(Entity);
@@ -66,20 +113,32 @@ fn handle_map_updates(
             && !work_order_queue.contains(&WorkOrder::Dig(*world_coordinates))
         {
             let entity = commands.spawn(WorkOrder::dig(*world_coordinates)).id();
-            work_order_queue
-                .pending
-                .push_back((entity, WorkOrder::Dig(*world_coordinates)));
+            work_order_queue.push(entity, WorkOrder::Dig(*world_coordinates));
         }
     }
 }
 
+type IdleWorker = (With<Worker>, Without<CurrentWorkOrder>);
+
+/// Hands the first pending work order to every idle worker, skipping those the worker is known
+/// not to reach. They stay pending until the map changes.
 fn fetch_new_work_order(
     mut work_order_queue: ResMut<WorkOrderQueue>,
-    query: Query<Entity, (With<Worker>, Without<CurrentWorkOrder>)>,
+    graph: Res<ChunkGraph>,
+    query: Query<(Entity, &WorldCoordinates), IdleWorker>,
     mut commands: Commands,
 ) {
-    for worker_entity in &query {
-        if let Some((work_order_entity, work_order)) = work_order_queue.pending.pop_front() {
+    for (worker_entity, coordinates) in &query {
+        // the regions are flooded once per worker, not once per work order
+        let reach = graph.regions().reach(coordinates.block());
+        let reachable = work_order_queue.pending.iter().position(|(_, work_order)| {
+            reach.as_ref().is_none_or(|reach| {
+                reach.reaches(graph.regions(), work_order.workplaces()) != Some(false)
+            })
+        });
+        if let Some((work_order_entity, work_order)) =
+            reachable.and_then(|index| work_order_queue.pending.remove(index))
+        {
             info!(
                 "dwarf is taking work order {:?} for entity {}",
                 work_order, work_order_entity
@@ -153,3 +212,41 @@ fn on_task_finished(
         }
     }
 }
+
+#[test]
+fn test_skip_unreachable_work_orders() {
+    use bevy::ecs::system::RunSystemOnce;
+    use map_generation::testing;
+
+    // a room walled in on the floor, with the worker outside
+    let mut world_map = testing::floor(0..16, 0..16);
+    testing::room(&mut world_map, IRect::new(8, 8, 12, 12));
+    let worker = IWorldCoordinates(IVec3::new(2, 2, 501));
+    let sealed = WorkOrder::Dig(IWorldCoordinates(IVec3::new(10, 10, 501)));
+    let open = WorkOrder::Dig(IWorldCoordinates(IVec3::new(4, 4, 501)));
+    let mut graph = ChunkGraph::default();
+    assert_eq!(
+        graph
+            .regions_mut()
+            .explore(&mut world_map, worker, sealed.workplaces()),
+        Some(false)
+    );
+
+    let mut world = World::new();
+    world.insert_resource(graph);
+    world.insert_resource(WorkOrderQueue::default());
+    world.spawn((Worker, WorldCoordinates::from(&worker)));
+    for work_order in [sealed, open] {
+        let WorkOrder::Dig(coordinates) = work_order;
+        let entity = world.spawn(WorkOrder::dig(coordinates)).id();
+        world
+            .resource_mut::<WorkOrderQueue>()
+            .push(entity, work_order);
+    }
+    world.run_system_once(fetch_new_work_order).unwrap();
+    let work_order_queue = world.resource::<WorkOrderQueue>();
+    assert_eq!(work_order_queue.in_progress.len(), 1);
+    assert_eq!(work_order_queue.in_progress[0].1, open);
+    assert_eq!(work_order_queue.pending.len(), 1);
+    assert_eq!(work_order_queue.pending[0].1, sealed);
+}
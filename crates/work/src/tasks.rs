@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use common::types::IWorldCoordinates;
+use common::coordinates::IWorldCoordinates;
 use dig::Dig;
 use walk_to::WalkTo;
 use walk_to_nearest::WalkToNearest;
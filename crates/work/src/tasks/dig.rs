@@ -1,8 +1,8 @@
 use bevy::prelude::*;
-use common::types::IWorldCoordinates;
+use common::coordinates::IWorldCoordinates;
 use map_generation::messages::{BlockUpdate, UpdateMap};
 
-use crate::tasks::Task;
+use crate::{Digger, tasks::Task};
 
 #[derive(Clone, Component, Copy, Debug, Reflect)]
 #[reflect(Component)]
@@ -21,13 +21,13 @@ impl Default for DigTimer {
 
 pub(crate) fn tick(
     time: Res<Time>,
-    mut query: Query<(&Dig, &mut DigTimer)>,
+    mut query: Query<(&Dig, &mut DigTimer, &Digger)>,
     mut message_writer: MessageWriter<UpdateMap>,
 ) {
-    for (dig, mut dig_timer) in &mut query {
+    for (dig, mut dig_timer, digger) in &mut query {
         dig_timer.tick(time.delta());
         if dig_timer.just_finished() {
-            message_writer.write(UpdateMap::DamageBlock(dig.0, 0.25));
+            message_writer.write(UpdateMap::DamageBlock(dig.0, digger.damage()));
             debug!("Hurting block {:?}", dig.0);
         }
     }
@@ -56,3 +56,34 @@ pub(crate) fn cleanup(
         }
     }
 }
+
+#[test]
+fn test_damage_follows_skill() {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    let block = IWorldCoordinates(IVec3::new(1, 2, 3));
+    let mut world = World::new();
+    world.init_resource::<Time>();
+    world.init_resource::<Messages<UpdateMap>>();
+    for skill in [0.5, 1.0, 2.0] {
+        let digger = Digger { skill };
+        world.spawn((Dig(block), digger));
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(0.25));
+        world.run_system_once(tick).unwrap();
+        let messages = world.resource::<Messages<UpdateMap>>();
+        let damage: Vec<f32> = messages
+            .iter_current_update_messages()
+            .map(|message| match message {
+                UpdateMap::DamageBlock(coordinates, damage) if *coordinates == block => *damage,
+                _ => panic!("only the block is damaged"),
+            })
+            .collect();
+        assert_eq!(damage, [digger.damage()]);
+        assert_eq!(digger.damage(), Digger::BASE_DAMAGE * skill);
+        world.clear_entities();
+        world.resource_mut::<Messages<UpdateMap>>().clear();
+    }
+}
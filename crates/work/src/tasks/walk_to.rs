@@ -1,11 +1,11 @@
 use bevy::prelude::*;
 use common::{
+    coordinates::{IWorldCoordinates, WorldCoordinates},
     traits::SpawnNamedObserver,
-    types::{IWorldCoordinates, WorldCoordinates},
 };
-use pathfinding::{path::Path, pathfinder::Pathfinder};
+use pathfinding::{PathEvent, PathState, pathfinder::Pathfinder};
 
-use super::Task;
+use super::{Task, TaskEvent, TaskState};
 
 #[derive(Clone, Component, Copy, Debug, Reflect)]
 #[reflect(Component)]
@@ -20,12 +20,22 @@ pub(crate) fn handle(query: Query<(Entity, &WorldCoordinates, &WalkTo)>, mut com
             .remove::<WalkTo>()
             .insert(Pathfinder::exact(start, walk_to.0))
             .id();
-        commands.spawn_named_observer(target, on_path_completed, "on_path_complete");
+        commands.spawn_named_observer(target, on_path_event, "on_path_event");
     }
 }
 
-fn on_path_completed(trigger: On<Remove, Path>, mut commands: Commands) {
-    commands.entity(trigger.entity).remove::<Task>();
+fn on_path_event(trigger: On<PathEvent>, mut commands: Commands) {
+    match trigger.state {
+        PathState::CalculationFailed => {
+            commands.trigger(TaskEvent {
+                entity: trigger.entity,
+                state: TaskState::Failed,
+            });
+        }
+        PathState::Completed => {
+            commands.entity(trigger.entity).remove::<Task>();
+        }
+    }
     debug!("despawning observer {}", trigger.observer());
     commands.entity(trigger.observer()).despawn();
 }
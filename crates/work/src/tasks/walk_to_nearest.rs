@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use common::{
-    functions::world_position_to_world_coordinates, traits::SpawnNamedObserver,
-    types::IWorldCoordinates,
+    coordinates::{IWorldCoordinates, WorldCoordinates},
+    traits::SpawnNamedObserver,
 };
 use pathfinding::{PathEvent, PathState, pathfinder::Pathfinder};
 
@@ -13,10 +13,13 @@ use super::{Task, TaskState};
 #[reflect(Component)]
 pub(crate) struct WalkToNearest(pub(crate) IWorldCoordinates);
 
-pub(crate) fn handle(query: Query<(Entity, &Transform, &WalkToNearest)>, mut commands: Commands) {
-    for (entity, transform, walk_to) in &query {
+pub(crate) fn handle(
+    query: Query<(Entity, &WorldCoordinates, &WalkToNearest)>,
+    mut commands: Commands,
+) {
+    for (entity, coordinates, walk_to) in &query {
         info!("inserting pathfinding component");
-        let start = world_position_to_world_coordinates(transform.translation);
+        let start = coordinates.block();
         let target = commands
             .entity(entity)
             .remove::<WalkToNearest>()
@@ -28,6 +28,19 @@ impl WorkOrderQueue {
                 .iter()
                 .any(|(_, work_order)| work_order == item)
     }
+
+    /// Adds a work order to the end of the pending queue.
+    pub fn push(&mut self, entity: Entity, work_order: WorkOrder) {
+        self.pending.push_back((entity, work_order));
+    }
+
+    /// Iterates over all work orders, in progress first, then pending.
+    pub fn iter(&self) -> impl Iterator<Item = &WorkOrder> {
+        self.in_progress
+            .iter()
+            .chain(self.pending.iter())
+            .map(|(_, work_order)| work_order)
+    }
 }
 
 fn unregister_work_order(
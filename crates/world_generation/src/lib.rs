@@ -1,3 +1,4 @@
+mod presets;
 mod world_generation;
 
 pub use world_generation::*;
@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use common::ron_file::{read, write};
+use map_generation::map_generation::WorldGenerationSettings;
+use serde::{Deserialize, Serialize};
+
+const PRESETS_PATH: &str = "saves/presets.ron";
+
+/// A named set of world generation settings.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Preset {
+    pub name: String,
+    pub settings: WorldGenerationSettings,
+}
+
+/// All presets that can be picked on the world generation screen.
+///
+/// Read from disk at startup, falling back to the built in presets.
+#[derive(Deserialize, Resource, Serialize)]
+pub struct Presets(pub Vec<Preset>);
+
+impl Default for Presets {
+    fn default() -> Self {
+        let default = WorldGenerationSettings::default();
+        Presets(vec![
+            Preset {
+                name: "Default".into(),
+                settings: default.clone(),
+            },
+            Preset {
+                name: "Islands".into(),
+                settings: WorldGenerationSettings {
+                    ocean_level: 30,
+                    terrain_scale: 24.0,
+                    ..default.clone()
+                },
+            },
+            Preset {
+                name: "Highlands".into(),
+                settings: WorldGenerationSettings {
+                    elevation: 160.0,
+                    roughness: 0.4,
+                    mineral_richness: 1.5,
+                    ..default.clone()
+                },
+            },
+            Preset {
+                name: "Caverns".into(),
+                settings: WorldGenerationSettings {
+                    cave_density: 0.08,
+                    mineral_richness: 2.0,
+                    ..default
+                },
+            },
+        ])
+    }
+}
+
+impl Presets {
+    pub fn load() -> Self {
+        Presets::load_from(Path::new(PRESETS_PATH))
+    }
+
+    /// Stores the settings under the given name, replacing any preset with the same name, and
+    /// writes all presets to disk.
+    pub fn save(&mut self, name: &str, settings: &WorldGenerationSettings) {
+        self.save_to(Path::new(PRESETS_PATH), name, settings);
+    }
+
+    fn load_from(path: &Path) -> Self {
+        if !path.exists() {
+            return Presets::default();
+        }
+        match read(path) {
+            Ok(presets) => presets,
+            Err(err) => {
+                error!("failed to load presets from {}: {}", path.display(), err);
+                Presets::default()
+            }
+        }
+    }
+
+    fn save_to(&mut self, path: &Path, name: &str, settings: &WorldGenerationSettings) {
+        let preset = Preset {
+            name: name.into(),
+            settings: settings.clone(),
+        };
+        match self.0.iter_mut().find(|preset| preset.name == name) {
+            Some(existing) => *existing = preset,
+            None => self.0.push(preset),
+        }
+        match write(path, self) {
+            Ok(()) => info!("saved preset {} to {}", name, path.display()),
+            Err(err) => error!("failed to save presets to {}: {}", path.display(), err),
+        }
+    }
+}
+
+#[test]
+fn test_presets_round_trip() {
+    let path = std::env::temp_dir().join("open_fortress_test_presets.ron");
+    let mut presets = Presets::default();
+    let settings = WorldGenerationSettings {
+        seed: 42,
+        elevation: 10.0,
+        cave_density: 0.5,
+        ..default()
+    };
+    presets.save_to(&path, "Flat", &settings);
+    // saving under the same name replaces the preset
+    presets.save_to(
+        &path,
+        "Flat",
+        &WorldGenerationSettings {
+            seed: 7,
+            ..settings
+        },
+    );
+    let loaded = Presets::load_from(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let names = |presets: &Presets| -> Vec<String> {
+        presets.0.iter().map(|preset| preset.name.clone()).collect()
+    };
+    assert_eq!(names(&loaded), names(&presets));
+    let flat = &loaded
+        .0
+        .iter()
+        .find(|preset| preset.name == "Flat")
+        .unwrap()
+        .settings;
+    assert_eq!(flat.seed, 7);
+    assert_eq!(flat.elevation, 10.0);
+    assert_eq!(flat.cave_density, 0.5);
+    assert_eq!(
+        flat.caverns.len(),
+        WorldGenerationSettings::default().caverns.len()
+    );
+}
+
+#[test]
+fn test_older_presets_load() {
+    // written before most settings existed
+    let path = std::env::temp_dir().join("open_fortress_test_older_presets.ron");
+    std::fs::write(
+        &path,
+        r#"([(name: "Old", settings: (seed: 3, elevation: 50.0))])"#,
+    )
+    .unwrap();
+    let loaded = Presets::load_from(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let default = WorldGenerationSettings::default();
+    let [old] = loaded.0.as_slice() else {
+        panic!("expected a single preset");
+    };
+    assert_eq!(old.name, "Old");
+    assert_eq!(old.settings.seed, 3);
+    assert_eq!(old.settings.elevation, 50.0);
+    assert_eq!(old.settings.map_size, default.map_size);
+    assert_eq!(old.settings.ocean_level, default.ocean_level);
+    assert_eq!(old.settings.caverns.len(), default.caverns.len());
+}
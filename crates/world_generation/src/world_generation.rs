@@ -1,13 +1,38 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, futures::check_ready},
+};
 use bevy_inspector_egui::{
     bevy_egui::{EguiContext, EguiPrimaryContextPass, PrimaryEguiContext},
     egui,
 };
 use common::states::AppState;
-use map_generation::map_generation::WorldGenerationSettings;
+use map_generation::{
+    map_generation::{EmbarkSite, WorldGenerationSettings},
+    overview::Overview,
+};
+
+use crate::presets::Presets;
+
+/// The overview shows the whole map, but is never wider or higher than this many pixels.
+const PREVIEW_SIZE: u32 = 256;
+
+/// The overview currently shown, uploaded to egui.
+struct Preview {
+    overview: Overview,
+    texture: egui::TextureHandle,
+}
+
+/// The overview shown and the one generated in the background, if any.
+#[derive(Default)]
+struct PreviewState {
+    shown: Option<Preview>,
+    generating: Option<Task<Overview>>,
+}
 
 pub fn plugin(app: &mut App) {
     app.insert_resource(WorldGenerationSettings::default())
+        .insert_resource(Presets::load())
         .add_systems(OnEnter(AppState::WorldGeneration), setup)
         .add_systems(
             EguiPrimaryContextPass,
@@ -21,14 +46,139 @@ fn setup(mut commands: Commands) {
 
 fn handle_ui(
     mut world_generation_settings: ResMut<WorldGenerationSettings>,
+    mut embark_site: ResMut<EmbarkSite>,
+    mut presets: ResMut<Presets>,
+    mut preset_name: Local<String>,
+    mut preview: Local<PreviewState>,
     context: Single<&mut EguiContext, With<PrimaryEguiContext>>,
     mut next_state: ResMut<NextState<AppState>>,
 ) {
     let mut egui_context = context.into_inner().clone();
     egui::Window::new("World Generation Settings").show(egui_context.get_mut(), |ui| {
-        ui.add(egui::DragValue::new(&mut world_generation_settings.seed).prefix("Seed: "));
+        egui::ComboBox::from_label("Preset")
+            .selected_text(preset_name.as_str())
+            .show_ui(ui, |ui| {
+                for preset in &presets.0 {
+                    if ui
+                        .selectable_label(*preset_name == preset.name, &preset.name)
+                        .clicked()
+                    {
+                        *preset_name = preset.name.clone();
+                        *world_generation_settings = preset.settings.clone();
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut *preset_name);
+            if ui.button("Save Preset").clicked() && !preset_name.is_empty() {
+                presets.save(&preset_name, &world_generation_settings);
+            }
+        });
+        ui.separator();
+        settings_ui(ui, &mut world_generation_settings);
+        ui.separator();
+        let mut embark_size = embark_site.0.size();
+        ui.horizontal(|ui| {
+            ui.label("Embark size");
+            ui.add(egui::DragValue::new(&mut embark_size.x).range(16..=256));
+            ui.add(egui::DragValue::new(&mut embark_size.y).range(16..=256));
+        });
+        if embark_size != embark_site.0.size() {
+            embark_site.0 = IRect::from_center_size(embark_site.0.center(), embark_size);
+        }
+        // a new task replaces the one still running, which is cancelled when dropped
+        if ui.button("Update Preview").clicked()
+            || preview.shown.is_none() && preview.generating.is_none()
+        {
+            let settings = world_generation_settings.clone();
+            preview.generating = Some(AsyncComputeTaskPool::get().spawn(async move {
+                let map_size = settings.map_size.max(UVec2::ONE);
+                let step = map_size.max_element().div_ceil(PREVIEW_SIZE);
+                Overview::generate(
+                    &settings,
+                    IVec2::ZERO,
+                    (map_size + step - 1) / step,
+                    step as i32,
+                )
+            }));
+        }
+        if let Some(task) = preview.generating.as_mut()
+            && let Some(overview) = check_ready(task)
+        {
+            preview.generating = None;
+            let image = egui::ColorImage::from_rgba_unmultiplied(
+                [overview.size.x as usize, overview.size.y as usize],
+                overview.pixels.as_flattened(),
+            );
+            let texture =
+                ui.ctx()
+                    .load_texture("world overview", image, egui::TextureOptions::NEAREST);
+            preview.shown = Some(Preview { overview, texture });
+        }
+        if preview.generating.is_some() {
+            ui.label("Generating preview...");
+        }
+        if let Some(preview) = preview.shown.as_ref() {
+            show_preview(ui, preview, &mut embark_site);
+        }
         if ui.button("Generate World").clicked() {
             next_state.set(AppState::MainGame);
         }
     });
 }
+
+fn settings_ui(ui: &mut egui::Ui, settings: &mut WorldGenerationSettings) {
+    ui.add(egui::DragValue::new(&mut settings.seed).prefix("Seed: "));
+    ui.horizontal(|ui| {
+        ui.label("Map size");
+        ui.add(egui::DragValue::new(&mut settings.map_size.x).range(64..=8192));
+        ui.add(egui::DragValue::new(&mut settings.map_size.y).range(64..=8192));
+    });
+    ui.add(egui::Slider::new(&mut settings.elevation, 10.0..=300.0).text("Elevation"));
+    ui.add(egui::Slider::new(&mut settings.terrain_scale, 4.0..=64.0).text("Terrain scale"));
+    ui.add(egui::Slider::new(&mut settings.roughness, 0.0..=1.0).text("Roughness"));
+    ui.add(egui::DragValue::new(&mut settings.ocean_level).prefix("Ocean level: "));
+    ui.add(egui::Slider::new(&mut settings.river_density, 0.0..=0.1).text("River density"));
+    ui.add(egui::Slider::new(&mut settings.cave_density, 0.0..=0.2).text("Cave density"));
+    ui.add(egui::Slider::new(&mut settings.mineral_richness, 0.0..=5.0).text("Mineral richness"));
+    let biomes = &mut settings.biomes;
+    ui.add(egui::Slider::new(&mut biomes.scale, 32.0..=1024.0).text("Biome scale"));
+    ui.add(egui::Slider::new(&mut biomes.temperature, -0.5..=0.5).text("Temperature"));
+    ui.add(egui::Slider::new(&mut biomes.rainfall, -0.5..=0.5).text("Rainfall"));
+    ui.add(egui::Slider::new(&mut biomes.vegetation, 0.0..=2.0).text("Vegetation"));
+    ui.add(egui::DragValue::new(&mut biomes.mountain_height).prefix("Mountain height: "));
+    for (index, layer) in settings.caverns.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("Cavern layer {}", index + 1));
+            ui.add(egui::DragValue::new(&mut layer.z).prefix("z: "));
+            ui.add(egui::DragValue::new(&mut layer.height).prefix("height: "));
+        });
+    }
+}
+
+/// Shows the overview with the embark site on top. Clicking on it moves the embark site.
+fn show_preview(ui: &mut egui::Ui, preview: &Preview, embark_site: &mut EmbarkSite) {
+    let overview = &preview.overview;
+    let size = egui::vec2(overview.size.x as f32, overview.size.y as f32);
+    let response =
+        ui.add(egui::Image::new((preview.texture.id(), size)).sense(egui::Sense::click_and_drag()));
+    let origin = response.rect.min;
+    if let Some(position) = response.interact_pointer_pos() {
+        let pixel = Vec2::new(position.x - origin.x, position.y - origin.y);
+        let center = overview.to_column(pixel.clamp(Vec2::ZERO, overview.size.as_vec2() - 1.0));
+        embark_site.0 = IRect::from_center_size(center, embark_site.0.size());
+    }
+    // the top left corner in pixels is the column with the lowest x and the highest y
+    let top_left = overview.to_pixel(IVec2::new(embark_site.0.min.x, embark_site.0.max.y));
+    let bottom_right = overview.to_pixel(IVec2::new(embark_site.0.max.x, embark_site.0.min.y));
+    let rect = egui::Rect::from_min_max(
+        origin + egui::vec2(top_left.x, top_left.y),
+        origin + egui::vec2(bottom_right.x, bottom_right.y),
+    );
+    ui.painter().rect_stroke(
+        rect,
+        0.0,
+        egui::Stroke::new(1.0, egui::Color32::RED),
+        egui::StrokeKind::Outside,
+    );
+}